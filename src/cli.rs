@@ -9,7 +9,7 @@ pub fn build_cli() -> Command {
                 .index(1) // this will always be the first positional argument
                 .num_args(1..)
                 .value_name("FILE")
-                .help("Input <FILE>."),
+                .help("Input <FILE>, a directory of (hive-partitioned) parquet files, or - to read standard input."),
         )
         .arg(
             Arg::new("names")
@@ -27,13 +27,18 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .conflicts_with("names"),
         )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Prints rows as newline-delimited JSON objects instead of space-separated text.")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("force")
                 .short('F')
                 .long("force")
-                .help("Overwrite existing keyword metadata if it is already present.")
-                .action(ArgAction::SetTrue)
-                .requires("insert-metadata"),
+                .help("Overwrite existing keyword metadata if it is already present, or an existing -o or --convert output (even the input file).")
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("tail")
@@ -99,7 +104,7 @@ pub fn build_cli() -> Command {
         .arg(
             Arg::new("list-kw-metadata")
             .long("list-keywords")
-            .help("Lists the keyword metadata of the given parquet file")
+            .help("Lists the keyword metadata of a parquet file, the header cards of a FITS file, the meta of an ECSV file, or the keywords of an IPAC table.")
             .action(ArgAction::SetTrue)
         )
         .arg(
@@ -109,6 +114,94 @@ pub fn build_cli() -> Command {
             .num_args(1)
             .value_name("KEYWORD")
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Reads the input as <FORMAT> instead of working it out from the file's contents and extension.")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(["parquet", "csv", "fits", "ipc", "json", "ndjson", "votable", "ecsv", "ipac"]),
+        )
+        .arg(
+            Arg::new("separator")
+                .long("separator")
+                .help("Splits csv columns on <SEPARATOR>: a single character, 'tab', 'space', or 'whitespace' for any run of spaces and tabs. Defaults to ',' (or tab for a .tsv file).")
+                .num_args(1)
+                .value_name("SEPARATOR"),
+        )
+        .arg(
+            Arg::new("no-header")
+                .long("no-header")
+                .help("The csv has no header line; columns are named column_1, column_2, ...")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("comment-prefix")
+                .long("comment-prefix")
+                .help("Skips csv lines starting with <PREFIX>, e.g. '#'.")
+                .num_args(1)
+                .value_name("PREFIX")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("null-values")
+                .long("null-values")
+                .help("Reads these csv <VALUE>s as nulls in every column. Multiple values can be comma separated, e.g. -99,NaN.")
+                .num_args(1)
+                .value_name("VALUE")
+                .value_delimiter(',')
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("skip-rows")
+                .long("skip-rows")
+                .help("Skips the first <N> lines of a csv before its header.")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("schema-overrides")
+                .long("schema-overrides")
+                .help("Reads csv columns as the given types instead of inferring them, e.g. id:str,mag:f32. Types are str, bool, i8-i64, u8-u64, f32 and f64.")
+                .num_args(1)
+                .value_name("COLUMN:TYPE")
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("infer-schema-length")
+                .long("infer-schema-length")
+                .help("Infers csv column types from the first <N> rows (default 100); 0 uses every row.")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("quote-char")
+                .long("quote-char")
+                .help("The csv quote character (default '\"'), or 'none' to read quotes as ordinary characters.")
+                .num_args(1)
+                .value_name("CHAR"),
+        )
+        .arg(
+            Arg::new("hdu")
+                .long("hdu")
+                .help("Reads the FITS extension given by <HDU> index or EXTNAME. Defaults to the first table, or the first image if there is none.")
+                .num_args(1)
+                .value_name("HDU"),
+        )
+        .arg(
+            Arg::new("list-hdus")
+                .long("list-hdus")
+                .help("Lists every HDU of a FITS file with its EXTNAME, type and dimensions.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("explode-vectors")
+                .long("explode-vectors")
+                .help("Splits FITS vector columns into one scalar column per element (name_0, name_1, ...).")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("stats")
                 .long("stats")
@@ -118,9 +211,87 @@ pub fn build_cli() -> Command {
         .arg(
             Arg::new("convert")
                 .long("convert")
-                .help("Attempts to convert csv and fits files into a parquet if it can.")
+                .help("Converts the file into a parquet (or, with --to, into another format) named <FILE>_converted.<EXTENSION>.")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("With --convert or -o, the <FORMAT> to write, whatever the <OUTFILE>'s extension.")
+                .num_args(1)
+                .value_name("FORMAT")
+                .value_parser(["parquet", "csv", "fits", "ipc", "json", "ndjson", "votable", "ecsv"])
+                .requires("output"),
+        )
+        .arg(
+            Arg::new("compression")
+                .long("compression")
+                .help("Compresses the pages of parquet outputs with <CODEC> (default zstd).")
+                .num_args(1)
+                .value_name("CODEC")
+                .value_parser(["uncompressed", "snappy", "gzip", "brotli", "zstd", "lz4"]),
+        )
+        .arg(
+            Arg::new("compression-level")
+                .long("compression-level")
+                .help("The gzip (0-9), brotli (0-11) or zstd (1-22) <LEVEL> for parquet outputs.")
+                .num_args(1)
+                .value_name("LEVEL")
+                .allow_negative_numbers(true)
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            Arg::new("row-group-size")
+                .long("row-group-size")
+                .help("Writes parquet outputs in row groups of at most <N> rows.")
+                .num_args(1)
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("data-page-size")
+                .long("data-page-size")
+                .help("The target size in <BYTES> of the data pages of parquet outputs (default 1 MiB).")
+                .num_args(1)
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("statistics")
+                .long("statistics")
+                .help("Whether parquet outputs store column min/max/null-count statistics (default on).")
+                .num_args(1)
+                .value_name("on|off")
+                .value_parser(["on", "off"]),
+        )
+        .arg(
+            Arg::new("dictionary")
+                .long("dictionary")
+                .help("on dictionary-encodes every string column of parquet outputs; off writes every column plain.")
+                .num_args(1)
+                .value_name("on|off")
+                .value_parser(["on", "off"]),
+        )
+        .arg(
+            Arg::new("header-keywords")
+                .long("header-keywords")
+                .help("When writing FITS from a parquet file, also copies its keyword metadata (e.g. maml) into COMMENT cards.")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("drop-keywords")
+                .long("drop-keywords")
+                .help("With -o, doesn't carry a parquet input's keyword metadata (e.g. maml) and column units into the parquet written.")
+                .requires("outfile")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("maml")
+                .long("maml")
+                .help("When writing parquet from a FITS table, VOTable, ECSV or IPAC file, also stores a MAML block generated from its header under the 'maml' keyword.")
+                .action(ArgAction::SetTrue)
+                .requires("output"),
+        )
         .arg(
             Arg::new("filter")
                 .long("filter")
@@ -134,7 +305,7 @@ pub fn build_cli() -> Command {
             Arg::new("outfile")
                 .long("outfile")
                 .short('o')
-                .help("Save the current selection to the <OUTFILE>.")
+                .help("Save the current selection, or the whole file in another format, to the <OUTFILE>. Unless --to says otherwise, a .fits <OUTFILE> is written as a FITS table, an .arrow, .feather or .ipc one as Arrow IPC, a .json one as a JSON array, an .ndjson or .jsonl one as newline-delimited JSON, a .vot or .xml one as a VOTable, an .ecsv one as Astropy ECSV, a .csv one as csv, anything else as parquet.")
                 .num_args(1)
                 .value_name("OUTFILE"),
         )
@@ -149,6 +320,7 @@ pub fn build_cli() -> Command {
                     "insert-metadata",
                     "delete-kw-metadata",
                     "list-kw-metadata",
+                    "list-hdus",
                     "summary",
                     "peak",
                     "stats",
@@ -158,4 +330,5 @@ pub fn build_cli() -> Command {
                 ])
                 .multiple(false),
         )
+        .group(ArgGroup::new("output").args(["convert", "outfile"]))
 }
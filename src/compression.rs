@@ -0,0 +1,176 @@
+//! Gzip, bzip2, zstd and xz compressed inputs and outputs.
+//!
+//! A compressed input is recognised by its magic bytes, or failing that by a
+//! `.gz`, `.bz2`, `.zst` or `.xz` extension, and decompressed into a temporary
+//! file that every reader can open (and seek in) as it would the original. An
+//! output whose name ends in one of those extensions is compressed as it is
+//! written.
+
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// The compression a file starts with the signature of.
+    pub fn from_magic(start: &[u8]) -> Option<Self> {
+        if start.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if start.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if start.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "bz2" => Some(Compression::Bzip2),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    /// The magic bytes if they are recognised, otherwise the extension.
+    pub fn detect(path: &Path) -> Result<Option<Self>> {
+        let mut start = Vec::with_capacity(6);
+        File::open(path)?.take(6).read_to_end(&mut start)?;
+        Ok(Compression::from_magic(&start).or_else(|| Compression::from_extension(path)))
+    }
+
+    fn decoder(self, file: File) -> Result<Box<dyn Read>> {
+        let file = BufReader::new(file);
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+            Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+        })
+    }
+}
+
+/// `cat.fits.gz` as `cat.fits`; names without a compression extension are
+/// returned as they are.
+pub fn without_compression_extension(path: &Path) -> PathBuf {
+    match Compression::from_extension(path) {
+        Some(_) => path.with_extension(""),
+        None => path.to_path_buf(),
+    }
+}
+
+/// A decompressed copy of a compressed input, deleted when it is dropped.
+pub struct Decompressed {
+    _dir: TempDir,
+    pub path: PathBuf,
+}
+
+/// Decompresses `path` if it is compressed. The copy keeps the file's name,
+/// less the compression extension, so its format can still be told from it.
+pub fn decompress(path: &Path) -> Result<Option<Decompressed>> {
+    if path.is_dir() {
+        return Ok(None);
+    }
+    let Some(compression) = Compression::detect(path)? else {
+        return Ok(None);
+    };
+    let dir = tempfile::Builder::new().prefix("dog-").tempdir()?;
+    let name = without_compression_extension(path);
+    let copy = dir.path().join(name.file_name().unwrap_or_default());
+    let mut decoder = compression.decoder(File::open(path)?)?;
+    let mut out = BufWriter::new(File::create(&copy)?);
+    if let Err(err) = io::copy(&mut decoder, &mut out) {
+        bail!("{path:?} could not be decompressed as {compression:?}: {err}");
+    }
+    out.flush()?;
+    Ok(Some(Decompressed {
+        _dir: dir,
+        path: copy,
+    }))
+}
+
+enum Encoder {
+    Plain(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Bzip2(bzip2::write::BzEncoder<BufWriter<File>>),
+    Zstd(zstd::stream::write::Encoder<'static, BufWriter<File>>),
+    Xz(xz2::write::XzEncoder<BufWriter<File>>),
+}
+
+/// A file being written, compressed if its name ends in a compression
+/// extension. `finish` has to be called for the file to be complete.
+pub struct OutputFile {
+    encoder: Encoder,
+}
+
+impl OutputFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let encoder = match Compression::from_extension(path) {
+            None => Encoder::Plain(file),
+            Some(Compression::Gzip) => Encoder::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Some(Compression::Bzip2) => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+                file,
+                bzip2::Compression::default(),
+            )),
+            Some(Compression::Zstd) => {
+                Encoder::Zstd(zstd::stream::write::Encoder::new(file, 0)?)
+            }
+            Some(Compression::Xz) => Encoder::Xz(xz2::write::XzEncoder::new(file, 6)),
+        };
+        Ok(OutputFile { encoder })
+    }
+
+    /// Writes the end of the compressed stream and flushes the file.
+    pub fn finish(self) -> Result<()> {
+        let mut file = match self.encoder {
+            Encoder::Plain(file) => file,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Bzip2(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Bzip2(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            Encoder::Plain(file) => file.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Bzip2(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Defaults from a YAML config file, for conventions that every file `dog`
+//! writes should follow without repeating them on the command line.
+//!
+//! The file is `$DOG_CONFIG` if that is set, otherwise `dog/config.yaml` in
+//! `$XDG_CONFIG_HOME` or `~/.config`. It is optional; options given on the
+//! command line take precedence over it.
+//!
+//! ```yaml
+//! parquet:
+//!   compression: zstd
+//!   compression-level: 9
+//!   row-group-size: 1000000
+//!   data-page-size: 1048576
+//!   statistics: on
+//!   dictionary: off
+//! ```
+
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::path::PathBuf;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::write::ParquetOptions;
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("DOG_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("dog").join("config.yaml"))
+}
+
+fn config() -> Result<Option<(PathBuf, Yaml)>> {
+    let Some(path) = config_path() else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path)?;
+    let documents = YamlLoader::load_from_str(&text)
+        .with_context(|| format!("{path:?} is not valid YAML"))?;
+    Ok(documents.into_iter().next().map(|config| (path, config)))
+}
+
+/// `on`/`off` as well as YAML's `true`/`false`.
+fn switch(value: &Yaml) -> Option<bool> {
+    match value {
+        Yaml::Boolean(on) => Some(*on),
+        Yaml::String(text) => match text.as_str() {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn size(value: &Yaml) -> Option<usize> {
+    value.as_i64().and_then(|size| usize::try_from(size).ok())
+}
+
+/// The `parquet` section of the config file, or nothing set if there isn't one.
+pub fn parquet_defaults() -> Result<ParquetOptions> {
+    let mut options = ParquetOptions::default();
+    let Some((path, config)) = config()? else {
+        return Ok(options);
+    };
+    let Some(section) = config["parquet"].as_hash() else {
+        return Ok(options);
+    };
+    for (key, value) in section {
+        let key = key.as_str().unwrap_or_default();
+        let parsed = match key {
+            "compression" => value.as_str().map(|codec| options.compression = Some(codec.to_string())),
+            "compression-level" => value.as_i64().map(|level| options.compression_level = Some(level)),
+            "row-group-size" => size(value).map(|rows| options.row_group_size = Some(rows)),
+            "data-page-size" => size(value).map(|bytes| options.data_page_size = Some(bytes)),
+            "statistics" => switch(value).map(|on| options.statistics = Some(on)),
+            "dictionary" => switch(value).map(|on| options.dictionary = Some(on)),
+            key => bail!("{path:?}: '{key}' is not a parquet option."),
+        };
+        if parsed.is_none() {
+            bail!("{path:?}: {value:?} is not a valid value for parquet '{key}'.");
+        }
+    }
+    Ok(options)
+}
@@ -0,0 +1,368 @@
+//! Astropy's Enhanced CSV (ECSV) format.
+//!
+//! An ECSV file is a csv table preceded by `#` lines holding a YAML header,
+//! the first of which is `# %ECSV 1.0`. The header's `datatype` list names each
+//! column with its dtype, unit and description, `delimiter` gives the separator
+//! (a space unless it says otherwise) and `meta` holds the table's metadata.
+//!
+//! | datatype | dtype |
+//! | --- | --- |
+//! | `bool` | Boolean |
+//! | `int8` ... `int64`, `uint8` ... `uint64` | Int8 ... UInt64 |
+//! | `float16` `float32` | Float32 |
+//! | `float64` `float128` | Float64 |
+//! | `string` `complex64` `complex128` `object` | String |
+//!
+//! A one-dimensional `subtype` such as `float64[3]` or `int64[null]` gives an
+//! Array or List column, decoded from the JSON text in each cell; deeper
+//! subtypes are left as that text.
+
+use anyhow::{anyhow, bail, Context, Result};
+use polars::prelude::*;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use yaml_rust2::yaml::{Array, Hash};
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+use crate::fits::FitsMetadata;
+use crate::fits_writer::storable_dtype;
+
+pub const ECSV_SIGNATURE: &str = "# %ECSV";
+
+struct EcsvColumn {
+    name: String,
+    datatype: String,
+    subtype: Option<String>,
+    unit: Option<String>,
+    description: Option<String>,
+}
+
+/// What the YAML header of an ECSV file says about the csv below it.
+pub struct EcsvHeader {
+    /// Separator of the csv rows.
+    pub delimiter: u8,
+    columns: Vec<EcsvColumn>,
+    meta: Yaml,
+}
+
+// The dtype an ECSV datatype is read as.
+fn dtype(datatype: &str) -> DataType {
+    match datatype {
+        "bool" => DataType::Boolean,
+        "int8" => DataType::Int8,
+        "int16" => DataType::Int16,
+        "int32" => DataType::Int32,
+        "int64" => DataType::Int64,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "float16" | "float32" => DataType::Float32,
+        "float64" | "float128" => DataType::Float64,
+        _ => DataType::String,
+    }
+}
+
+// Scalars as text; mappings and sequences are `None`.
+fn scalar_text(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// A scalar as it is, anything else as the YAML block it was written as.
+fn yaml_text(value: &Yaml) -> Result<String> {
+    if let Some(text) = scalar_text(value) {
+        return Ok(text);
+    }
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(value)
+        .map_err(|e| anyhow!("{e}"))?;
+    Ok(out.trim_start_matches("---").trim_start_matches('\n').to_string())
+}
+
+impl EcsvHeader {
+    /// Dtypes of the columns whose datatype polars would not infer by itself.
+    pub fn schema(&self) -> Schema {
+        self.columns
+            .iter()
+            .map(|column| {
+                let dtype = match column.subtype {
+                    Some(_) => DataType::String,
+                    None => dtype(&column.datatype),
+                };
+                Field::new(column.name.as_str().into(), dtype)
+            })
+            .collect()
+    }
+
+    /// Turns the JSON text of one-dimensional subtype columns into Array or
+    /// List columns. Casting is not strict, so `null` elements become nulls.
+    pub fn vector_columns(&self) -> Vec<Expr> {
+        self.columns
+            .iter()
+            .filter_map(|column| {
+                let (leaf, shape) = column.subtype.as_deref()?.split_once('[')?;
+                let shape = shape.strip_suffix(']')?;
+                if shape.contains(',') {
+                    return None;
+                }
+                let leaf = Box::new(dtype(leaf));
+                let dtype = match shape.trim().parse::<usize>() {
+                    Ok(width) => DataType::Array(leaf, width),
+                    Err(_) => DataType::List(leaf),
+                };
+                Some(
+                    col(column.name.as_str())
+                        .str()
+                        .strip_chars(lit("[] "))
+                        .str()
+                        .split(lit(","))
+                        .cast(dtype),
+                )
+            })
+            .collect()
+    }
+
+    /// `meta` entries as keyword and text. Astropy usually writes `meta` as an
+    /// `!!omap`, a sequence of one-entry mappings, so both forms are read.
+    pub fn keywords(&self) -> Result<Vec<(String, String)>> {
+        let entries: Vec<(&Yaml, &Yaml)> = match &self.meta {
+            Yaml::Hash(hash) => hash.iter().collect(),
+            Yaml::Array(items) => items
+                .iter()
+                .filter_map(Yaml::as_hash)
+                .flat_map(|hash| hash.iter())
+                .collect(),
+            _ => Vec::new(),
+        };
+        entries
+            .into_iter()
+            .filter_map(|(key, value)| Some((scalar_text(key)?, value)))
+            .map(|(key, value)| Ok((key, yaml_text(value)?)))
+            .collect()
+    }
+
+    /// The table's `name` from `meta` and the unit and description of every
+    /// column, to carry into another format.
+    pub fn metadata(&self) -> Result<FitsMetadata> {
+        let extname = self
+            .keywords()?
+            .into_iter()
+            .find_map(|(key, value)| (key == "name").then_some(value));
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                let described = [("unit", &column.unit), ("description", &column.description)]
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+                    .collect::<Vec<_>>();
+                (column.name.clone(), described)
+            })
+            .filter(|(_, described)| !described.is_empty())
+            .collect();
+        Ok(FitsMetadata {
+            extname,
+            header: Vec::new(),
+            columns,
+        })
+    }
+}
+
+/// The YAML header of an ECSV file, or `None` if the file doesn't open with
+/// `# %ECSV`.
+pub fn read_ecsv_header(path: &Path) -> Result<Option<EcsvHeader>> {
+    let mut lines = BufReader::new(File::open(path)?).lines();
+    match lines.next().transpose()? {
+        Some(line) if line.starts_with(ECSV_SIGNATURE) => {}
+        _ => return Ok(None),
+    }
+    let mut yaml = String::new();
+    for line in lines {
+        let line = line?;
+        let Some(text) = line.strip_prefix('#') else {
+            break;
+        };
+        yaml.push_str(text.strip_prefix(' ').unwrap_or(text));
+        yaml.push('\n');
+    }
+    let doc = YamlLoader::load_from_str(&yaml)
+        .map_err(|e| anyhow!("{path:?} has a malformed ECSV header: {e}"))?
+        .into_iter()
+        .next()
+        .unwrap_or(Yaml::Null);
+
+    let delimiter = match doc["delimiter"].as_str() {
+        None => b' ',
+        Some(d) if d.len() == 1 => d.as_bytes()[0],
+        Some(d) => bail!("{path:?} has an ECSV delimiter of '{d}'; only one character is allowed."),
+    };
+    let Some(datatypes) = doc["datatype"].as_vec() else {
+        bail!("{path:?} has no datatype list in its ECSV header.");
+    };
+    let columns = datatypes
+        .iter()
+        .map(|entry| {
+            Ok(EcsvColumn {
+                name: scalar_text(&entry["name"])
+                    .context("an ECSV datatype entry has no name")?,
+                datatype: scalar_text(&entry["datatype"]).unwrap_or_else(|| "string".to_string()),
+                subtype: scalar_text(&entry["subtype"]),
+                unit: scalar_text(&entry["unit"]),
+                description: scalar_text(&entry["description"]),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(EcsvHeader {
+        delimiter,
+        columns,
+        meta: doc["meta"].clone(),
+    }))
+}
+
+/// `meta` keywords of an ECSV file, in the order they were written.
+pub fn read_ecsv_keywords(path: &Path) -> Result<Vec<(String, String)>> {
+    match read_ecsv_header(path)? {
+        Some(header) => header.keywords(),
+        None => bail!("{path:?} is not an ECSV file."),
+    }
+}
+
+/// Table name, units and descriptions of an ECSV file.
+pub fn read_ecsv_metadata(path: &Path) -> Result<FitsMetadata> {
+    match read_ecsv_header(path)? {
+        Some(header) => header.metadata(),
+        None => Ok(FitsMetadata::default()),
+    }
+}
+
+// ECSV name of a dtype, after `storable_dtype` has removed the ones with none.
+fn datatype_name(dtype: &DataType) -> Option<&'static str> {
+    Some(match dtype {
+        DataType::Boolean => "bool",
+        DataType::Int8 => "int8",
+        DataType::Int16 => "int16",
+        DataType::Int32 => "int32",
+        DataType::Int64 => "int64",
+        DataType::UInt8 => "uint8",
+        DataType::UInt16 => "uint16",
+        DataType::UInt32 => "uint32",
+        DataType::UInt64 => "uint64",
+        DataType::Float32 => "float32",
+        DataType::Float64 => "float64",
+        DataType::String => "string",
+        _ => return None,
+    })
+}
+
+// `float64[3,2]` for nested arrays, with `null` for a dimension that varies.
+fn subtype(dtype: &DataType) -> Option<String> {
+    let mut dims = Vec::new();
+    let mut leaf = dtype;
+    loop {
+        match leaf {
+            DataType::Array(inner, width) => {
+                dims.push(width.to_string());
+                leaf = inner;
+            }
+            DataType::List(inner) => {
+                dims.push("null".to_string());
+                leaf = inner;
+            }
+            _ => break,
+        }
+    }
+    Some(format!("{}[{}]", datatype_name(leaf)?, dims.join(",")))
+}
+
+fn json_text(value: AnyValue) -> String {
+    match value {
+        AnyValue::Null => "null".to_string(),
+        AnyValue::String(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+        AnyValue::StringOwned(s) => json_text(AnyValue::String(s.as_str())),
+        AnyValue::List(s) | AnyValue::Array(s, _) => {
+            let elements: Vec<String> = s.iter().map(json_text).collect();
+            format!("[{}]", elements.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// The `# %ECSV` header describing `df`, and `df` ready to be written below it
+/// as csv: booleans as `True`/`False` and nested columns as JSON text.
+pub fn ecsv_frame(df: DataFrame, metadata: &FitsMetadata) -> Result<(String, DataFrame)> {
+    let mut datatypes = Array::new();
+    let mut columns = Vec::with_capacity(df.width());
+    for column in df.columns() {
+        let series = column.as_materialized_series();
+        let series = series.cast(&storable_dtype(series.dtype()))?;
+        let name = series.name().to_string();
+
+        let mut entry = Hash::new();
+        entry.insert(Yaml::String("name".into()), Yaml::String(name.clone()));
+        let (datatype, subtype) = match series.dtype() {
+            dtype if dtype.is_nested() => match subtype(dtype) {
+                Some(subtype) => ("string", Some(subtype)),
+                None => bail!("column '{name}' of type {dtype} can't be written to ECSV"),
+            },
+            dtype => match datatype_name(dtype) {
+                Some(datatype) => (datatype, None),
+                None => bail!("column '{name}' of type {dtype} can't be written to ECSV"),
+            },
+        };
+        entry.insert(Yaml::String("datatype".into()), Yaml::String(datatype.into()));
+        if let Some(subtype) = subtype {
+            entry.insert(Yaml::String("subtype".into()), Yaml::String(subtype));
+        }
+        for (key, value) in metadata.column(&name).unwrap_or_default() {
+            if key == "unit" || key == "description" {
+                entry.insert(Yaml::String(key.clone()), Yaml::String(value.clone()));
+            }
+        }
+        datatypes.push(Yaml::Hash(entry));
+
+        let series = match series.dtype() {
+            DataType::Boolean => series
+                .bool()?
+                .iter()
+                .map(|v| v.map(|b| if b { "True" } else { "False" }))
+                .collect::<StringChunked>()
+                .with_name(series.name().clone())
+                .into_series(),
+            dtype if dtype.is_nested() => series
+                .iter()
+                .map(|value| (!value.is_null()).then(|| json_text(value)))
+                .collect::<StringChunked>()
+                .with_name(series.name().clone())
+                .into_series(),
+            _ => series,
+        };
+        columns.push(series.into_column());
+    }
+
+    let mut doc = Hash::new();
+    doc.insert(Yaml::String("delimiter".into()), Yaml::String(",".into()));
+    doc.insert(Yaml::String("datatype".into()), Yaml::Array(datatypes));
+    if let Some(name) = &metadata.extname {
+        let mut meta = Hash::new();
+        meta.insert(Yaml::String("name".into()), Yaml::String(name.clone()));
+        doc.insert(Yaml::String("meta".into()), Yaml::Hash(meta));
+    }
+    doc.insert(Yaml::String("schema".into()), Yaml::String("astropy-2.0".into()));
+
+    let mut yaml = String::new();
+    YamlEmitter::new(&mut yaml)
+        .dump(&Yaml::Hash(doc))
+        .map_err(|e| anyhow!("{e}"))?;
+    let mut header = format!("{ECSV_SIGNATURE} 1.0\n");
+    for line in yaml.lines() {
+        header.push_str(&format!("# {line}\n"));
+    }
+    Ok((header, DataFrame::new(df.height(), columns)?))
+}
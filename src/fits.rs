@@ -0,0 +1,1406 @@
+//! Reading FITS files into polars.
+//!
+//! Only the headers are read up front. A table becomes a lazy scan that reads
+//! its rows in batches, stopping once `-H` has enough of them, and decodes just
+//! the projected columns (in parallel with `fitsio_pure_tsl`'s core API). FITS
+//! stores a table row by row, so whole rows are still read from disk, as is
+//! the whole heap when a `P`/`Q` column is wanted: `-c` saves decoding, not
+//! reading. The batches are stacked into one DataFrame, since polars only runs
+//! anonymous scans on its in-memory engine.
+//! Every standard binary-table TFORM code is mapped to a polars dtype:
+//!
+//! | TFORM | dtype |
+//! | --- | --- |
+//! | `L` | Boolean |
+//! | `X` | Boolean for `1X`, the smallest unsigned integer holding the bits up to `64X`, one Boolean per bit beyond that |
+//! | `B` `I` `J` `K` | UInt8, Int16, Int32, Int64 |
+//! | `E` `D` | Float32, Float64 |
+//! | `C` `M` | Struct of `re`/`im` Float32 or Float64 |
+//! | `A` | String |
+//! | `P` `Q` | List of the element type (String for `PA`/`QA`) |
+//!
+//! A repeat count above one (other than for `A`) gives an Array column, nested
+//! to the `TDIMn` shape if there is one. `--explode-vectors` instead splits it
+//! into `name_0..name_N` scalar columns.
+//!
+//! Fixed-width numeric columns are returned as physical values. `TNULLn` and
+//! NaN become nulls, the standard `TZEROn` offsets give Int8 for `B` and
+//! UInt16/UInt32/UInt64 for `I`/`J`/`K`, and any other `TSCALn`/`TZEROn` pair
+//! produces Float64.
+//!
+//! ASCII (`XTENSION= 'TABLE'`) extensions are read too. `Aw` fields become
+//! String, `Iw` Int64 and `Fw.d`/`Ew.d`/`Dw.d` Float64, parsed the Fortran way
+//! (a `D` exponent, or an implied decimal point `d` digits from the right when
+//! the field has none). Blank numeric fields and fields matching the `TNULLn`
+//! string are null.
+//!
+//! An image HDU reads as a long-form pixel table: one row per pixel, with its
+//! 1-based coordinates in `x`, `y`, `z` (`axis4` and so on beyond three axes)
+//! and the physical pixel in `value`. BITPIX, BSCALE, BZERO and BLANK play the
+//! same part as a column's TFORM, TSCALn, TZEROn and TNULLn. `image_summary`
+//! streams through the pixels instead, for `--summary` and `--stats`.
+
+use anyhow::{anyhow, bail, Result};
+use fitsio_pure_tsl::bintable::{
+    extract_column_scaling, parse_binary_table_columns, read_binary_column, BinaryColumnData,
+    BinaryColumnDescriptor, BinaryColumnType,
+};
+use fitsio_pure_tsl::hdu::{FitsData, Hdu, HduInfo};
+use fitsio_pure_tsl::header::{format_card, header_byte_len, parse_header_blocks, Card};
+use fitsio_pure_tsl::table::{parse_ascii_table_columns, AsciiColumnDescriptor, AsciiColumnFormat};
+use fitsio_pure_tsl::value::Value;
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::any::Any;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::reader::ReadOptions;
+
+/// One line of the `--list-hdus` output.
+pub struct HduSummary {
+    pub index: usize,
+    pub extname: Option<String>,
+    pub kind: &'static str,
+    pub dimensions: String,
+}
+
+/// A header card as printed by `-k` and `--list-keywords`.
+pub struct HeaderCard {
+    pub keyword: String,
+    pub value: Option<String>,
+    pub comment: Option<String>,
+}
+
+fn card_string(cards: &[Card], keyword: &str) -> Option<String> {
+    cards.iter().find_map(|card| match &card.value {
+        Some(Value::String(s)) if card.keyword_str() == keyword => Some(s.trim().to_string()),
+        _ => None,
+    })
+}
+
+fn card_integer(cards: &[Card], keyword: &str) -> Option<i64> {
+    cards.iter().find_map(|card| match &card.value {
+        Some(Value::Integer(n)) if card.keyword_str() == keyword => Some(*n),
+        _ => None,
+    })
+}
+
+fn card_number(cards: &[Card], keyword: &str) -> Option<f64> {
+    cards.iter().find_map(|card| match &card.value {
+        Some(Value::Integer(n)) if card.keyword_str() == keyword => Some(*n as f64),
+        Some(Value::Float(f)) if card.keyword_str() == keyword => Some(*f),
+        _ => None,
+    })
+}
+
+fn hdu_kind(hdu: &Hdu) -> &'static str {
+    match hdu.info {
+        HduInfo::Primary { .. } => "primary image",
+        HduInfo::Image { .. } => "image",
+        HduInfo::AsciiTable { .. } => "ASCII table",
+        HduInfo::BinaryTable { .. } => "binary table",
+        HduInfo::RandomGroups { .. } => "random groups",
+        HduInfo::CompressedImage { .. } => "compressed image",
+    }
+}
+
+fn hdu_dimensions(hdu: &Hdu) -> String {
+    let axes = |naxes: &[usize]| {
+        if naxes.is_empty() {
+            "no data".to_string()
+        } else {
+            naxes
+                .iter()
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(" x ")
+        }
+    };
+    match &hdu.info {
+        HduInfo::Primary { naxes, .. }
+        | HduInfo::Image { naxes, .. }
+        | HduInfo::RandomGroups { naxes, .. } => axes(naxes),
+        HduInfo::CompressedImage { znaxes, .. } => axes(znaxes),
+        HduInfo::AsciiTable {
+            naxis2, tfields, ..
+        }
+        | HduInfo::BinaryTable {
+            naxis2, tfields, ..
+        } => {
+            format!("{naxis2} rows x {tfields} columns")
+        }
+    }
+}
+
+const BLOCK_SIZE: usize = 2880;
+
+// Raw table bytes decoded per batch while scanning.
+const BATCH_BYTES: usize = 64 << 20;
+
+fn axes(cards: &[Card], prefix: &str) -> Result<Vec<usize>> {
+    let count = card_integer(cards, &format!("{prefix}NAXIS"))
+        .ok_or_else(|| anyhow!("{prefix}NAXIS is missing"))?;
+    (1..=count)
+        .map(|i| {
+            card_integer(cards, &format!("{prefix}NAXIS{i}"))
+                .map(|n| n as usize)
+                .ok_or_else(|| anyhow!("{prefix}NAXIS{i} is missing"))
+        })
+        .collect()
+}
+
+// The HDU type and shape, from the mandatory keywords of its header.
+fn hdu_info(cards: &[Card], is_primary: bool) -> Result<HduInfo> {
+    let integer =
+        |keyword: &str| card_integer(cards, keyword).ok_or_else(|| anyhow!("{keyword} is missing"));
+    let bitpix = integer("BITPIX")?;
+    if is_primary {
+        let naxes = axes(cards, "")?;
+        let groups = cards
+            .iter()
+            .any(|card| card.keyword_str() == "GROUPS" && card.value == Some(Value::Logical(true)));
+        if groups && naxes.first() == Some(&0) {
+            return Ok(HduInfo::RandomGroups {
+                bitpix,
+                naxes: naxes[1..].to_vec(),
+                pcount: integer("PCOUNT")? as usize,
+                gcount: integer("GCOUNT")? as usize,
+            });
+        }
+        return Ok(HduInfo::Primary { bitpix, naxes });
+    }
+
+    let table = || -> Result<(usize, usize, usize)> {
+        Ok((
+            integer("NAXIS1")? as usize,
+            integer("NAXIS2")? as usize,
+            integer("TFIELDS")? as usize,
+        ))
+    };
+    match card_string(cards, "XTENSION").as_deref() {
+        Some("IMAGE") => Ok(HduInfo::Image {
+            bitpix,
+            naxes: axes(cards, "")?,
+        }),
+        Some("TABLE") => {
+            let (naxis1, naxis2, tfields) = table()?;
+            Ok(HduInfo::AsciiTable {
+                naxis1,
+                naxis2,
+                tfields,
+            })
+        }
+        Some("BINTABLE") => {
+            let (naxis1, naxis2, tfields) = table()?;
+            let pcount = integer("PCOUNT")? as usize;
+            let compressed = cards.iter().any(|card| {
+                card.keyword_str() == "ZIMAGE" && card.value == Some(Value::Logical(true))
+            });
+            if !compressed {
+                return Ok(HduInfo::BinaryTable {
+                    naxis1,
+                    naxis2,
+                    pcount,
+                    tfields,
+                });
+            }
+            let znaxes = axes(cards, "Z")?;
+            Ok(HduInfo::CompressedImage {
+                zbitpix: integer("ZBITPIX")?,
+                ztile: (1..=znaxes.len())
+                    .map(|i| {
+                        let whole_row = if i == 1 { znaxes[0] } else { 1 };
+                        card_integer(cards, &format!("ZTILE{i}")).map_or(whole_row, |n| n as usize)
+                    })
+                    .collect(),
+                znaxes,
+                zcmptype: card_string(cards, "ZCMPTYPE").unwrap_or_default(),
+                blocksize: card_integer(cards, "ZVAL1").unwrap_or(32) as usize,
+                rice_bytepix: card_integer(cards, "ZVAL2").unwrap_or(4) as usize,
+                naxis1,
+                naxis2,
+                pcount,
+                tfields,
+            })
+        }
+        Some(other) => bail!("unsupported XTENSION '{other}'"),
+        None => bail!("XTENSION is missing"),
+    }
+}
+
+// Size of the data unit, without the padding to a whole block.
+fn data_byte_len(info: &HduInfo) -> usize {
+    let pixels = |bitpix: i64, naxes: &[usize]| {
+        if naxes.is_empty() {
+            0
+        } else {
+            bitpix.unsigned_abs() as usize / 8 * naxes.iter().product::<usize>()
+        }
+    };
+    match info {
+        HduInfo::Primary { bitpix, naxes } | HduInfo::Image { bitpix, naxes } => {
+            pixels(*bitpix, naxes)
+        }
+        HduInfo::RandomGroups {
+            bitpix,
+            naxes,
+            pcount,
+            gcount,
+        } => {
+            bitpix.unsigned_abs() as usize / 8 * gcount * (pcount + naxes.iter().product::<usize>())
+        }
+        HduInfo::AsciiTable { naxis1, naxis2, .. } => naxis1 * naxis2,
+        HduInfo::BinaryTable {
+            naxis1,
+            naxis2,
+            pcount,
+            ..
+        }
+        | HduInfo::CompressedImage {
+            naxis1,
+            naxis2,
+            pcount,
+            ..
+        } => naxis1 * naxis2 + pcount,
+    }
+}
+
+// Reads the header of every HDU, seeking over the data units, so opening even a
+// very large file only costs a few blocks. As with `fitsio_pure_tsl::parse_fits`,
+// a damaged or truncated HDU after the first one, such as trailing padding or
+// junk, ends the list instead of failing.
+fn open_fits(path: &Path) -> Result<FitsData> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len() as usize;
+    let mut hdus: Vec<Hdu> = Vec::new();
+    let mut offset = 0;
+
+    while offset + BLOCK_SIZE <= file_len {
+        let hdu = match read_header(&mut file, offset, hdus.is_empty()) {
+            Ok(hdu) => hdu,
+            Err(_) if !hdus.is_empty() => break,
+            Err(e) => bail!("{}: {e}", path.display()),
+        };
+        if hdu.data_start + hdu.data_len > file_len {
+            if !hdus.is_empty() {
+                break;
+            }
+            bail!("{}: HDU {} is truncated.", path.display(), hdus.len());
+        }
+        offset = hdu.data_start + hdu.data_len.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        hdus.push(hdu);
+    }
+    if hdus.is_empty() {
+        bail!("{}: not a FITS file.", path.display());
+    }
+    Ok(FitsData { hdus })
+}
+
+fn read_header(file: &mut File, offset: usize, is_primary: bool) -> Result<Hdu> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut header = Vec::new();
+    let header_len = loop {
+        let mut block = [0; BLOCK_SIZE];
+        file.read_exact(&mut block)
+            .map_err(|_| anyhow!("header has no END card"))?;
+        header.extend_from_slice(&block);
+        if let Ok(len) = header_byte_len(&header) {
+            break len;
+        }
+    };
+    let cards = parse_header_blocks(&header[..header_len]).map_err(|e| anyhow!("{e}"))?;
+    if is_primary && cards.first().map(Card::keyword_str) != Some("SIMPLE") {
+        bail!("the first HDU must start with SIMPLE");
+    }
+    let info = hdu_info(&cards, is_primary)?;
+    Ok(Hdu {
+        data_len: data_byte_len(&info),
+        info,
+        header_start: offset,
+        data_start: offset + header_len,
+        cards,
+    })
+}
+
+fn summarise(index: usize, hdu: &Hdu) -> HduSummary {
+    HduSummary {
+        index,
+        extname: card_string(&hdu.cards, "EXTNAME"),
+        kind: hdu_kind(hdu),
+        dimensions: hdu_dimensions(hdu),
+    }
+}
+
+/// Summarises every HDU in a FITS file: index, EXTNAME, type and dimensions.
+pub fn list_fits_hdus(path: &Path) -> Result<Vec<HduSummary>> {
+    let fits = open_fits(path)?;
+    Ok(fits
+        .iter()
+        .enumerate()
+        .map(|(index, hdu)| summarise(index, hdu))
+        .collect())
+}
+
+// Finds the HDU named by `--hdu`, either an index or an EXTNAME.
+fn find_hdu(fits: &FitsData, selector: &str) -> Result<usize> {
+    match selector.trim().parse::<usize>() {
+        Ok(index) if index < fits.len() => Ok(index),
+        Ok(index) => bail!(
+            "HDU {index} does not exist; the file only has {} HDUs (0-{}).",
+            fits.len(),
+            fits.len() - 1
+        ),
+        Err(_) => fits
+            .iter()
+            .position(|hdu| {
+                card_string(&hdu.cards, "EXTNAME")
+                    .is_some_and(|name| name.eq_ignore_ascii_case(selector.trim()))
+            })
+            .ok_or_else(|| {
+                anyhow!("No HDU has EXTNAME '{selector}'. Run `dog --list-hdus` to see them.")
+            }),
+    }
+}
+
+fn is_table(hdu: &Hdu) -> bool {
+    matches!(
+        hdu.info,
+        HduInfo::BinaryTable { .. } | HduInfo::AsciiTable { .. }
+    )
+}
+
+// The shape and BITPIX of an image HDU that has pixels in it.
+fn image_shape(hdu: &Hdu) -> Option<(i64, &[usize])> {
+    match &hdu.info {
+        HduInfo::Primary { bitpix, naxes } | HduInfo::Image { bitpix, naxes }
+            if !naxes.is_empty() && naxes.iter().all(|&n| n > 0) =>
+        {
+            Some((*bitpix, naxes))
+        }
+        _ => None,
+    }
+}
+
+// The first table, or failing that the first image, so that `dog image.fits`
+// shows the pixels rather than an error.
+fn default_hdu(fits: &FitsData) -> Option<usize> {
+    fits.iter()
+        .position(is_table)
+        .or_else(|| fits.iter().position(|hdu| image_shape(hdu).is_some()))
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::Logical(b) => if *b { "T" } else { "F" }.to_string(),
+        Value::Integer(n) => n.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::String(s) => s.trim_end().to_string(),
+        Value::ComplexInt(re, im) => format!("({re}, {im})"),
+        Value::ComplexFloat(re, im) => format!("({re}, {im})"),
+    }
+}
+
+/// Header cards of the primary HDU and, when it is a different HDU, of the one
+/// picked by `--hdu` (the first table by default), in file order.
+/// Commentary cards (HISTORY, COMMENT) carry their text as the comment.
+pub fn fits_headers(
+    path: &Path,
+    hdu_selector: Option<&str>,
+) -> Result<Vec<(HduSummary, Vec<HeaderCard>)>> {
+    let fits = open_fits(path)?;
+    let selected = match hdu_selector {
+        Some(selector) => find_hdu(&fits, selector)?,
+        None => default_hdu(&fits).unwrap_or(0),
+    };
+    let mut indices = vec![0];
+    if selected != 0 {
+        indices.push(selected);
+    }
+    Ok(indices
+        .into_iter()
+        .map(|index| {
+            let hdu = &fits.hdus[index];
+            let cards = hdu
+                .cards
+                .iter()
+                .filter(|card| !card.is_end())
+                .map(|card| HeaderCard {
+                    keyword: card.keyword_str().to_string(),
+                    value: card.value.as_ref().map(value_text),
+                    comment: card.comment.as_ref().map(|c| c.trim().to_string()),
+                })
+                .collect();
+            (summarise(index, hdu), cards)
+        })
+        .collect())
+}
+
+/// What `--convert` carries over from a FITS table besides the data.
+#[derive(Default)]
+pub struct FitsMetadata {
+    /// EXTNAME of the table, if it has one.
+    pub extname: Option<String>,
+    /// Primary and table header cards as 80-column card images, without the
+    /// keywords that only describe the binary-table layout.
+    pub header: Vec<String>,
+    /// `unit`, `ucd` and `description` (from TUNITn, TUCDn, TCOMMn) per column.
+    pub columns: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl FitsMetadata {
+    /// Description of a column, also matching the `name_N` columns that
+    /// `--explode-vectors` splits a vector column into.
+    pub fn column(&self, name: &str) -> Option<&[(String, String)]> {
+        let find = |name: &str| {
+            self.columns
+                .iter()
+                .find(|(column, _)| column == name)
+                .map(|(_, described)| described.as_slice())
+        };
+        find(name).or_else(|| {
+            let (stem, element) = name.rsplit_once('_')?;
+            element.parse::<usize>().ok().and_then(|_| find(stem))
+        })
+    }
+}
+
+// Keywords that describe how the HDU is laid out rather than what is in it,
+// and checksums of its bytes; these are meaningless once the data has been
+// converted.
+pub(crate) fn is_layout_keyword(keyword: &str) -> bool {
+    const LAYOUT: [&str; 17] = [
+        "SIMPLE", "EXTEND", "XTENSION", "BITPIX", "NAXIS", "PCOUNT", "GCOUNT", "TFIELDS", "THEAP",
+        "BSCALE", "BZERO", "BLANK", "END", "CHECKSUM", "DATASUM", "ZHECKSUM", "ZDATASUM",
+    ];
+    const COLUMN: [&str; 11] = [
+        "TTYPE", "TFORM", "TDIM", "TSCAL", "TZERO", "TNULL", "TBCOL", "TUNIT", "TUCD", "TCOMM",
+        "TDISP",
+    ];
+    let stem = keyword.trim_end_matches(|c: char| c.is_ascii_digit());
+    LAYOUT.contains(&keyword) || (stem != keyword && (stem == "NAXIS" || COLUMN.contains(&stem)))
+}
+
+/// Header cards and column descriptions of the table `read_fits_file` reads.
+pub fn read_fits_metadata(path: &Path, hdu_selector: Option<&str>) -> Result<FitsMetadata> {
+    let fits = open_fits(path)?;
+    let index = resolve_hdu(&fits, hdu_selector)?;
+    let table = &fits.hdus[index];
+    let tfields = match table.info {
+        HduInfo::BinaryTable { tfields, .. } | HduInfo::AsciiTable { tfields, .. } => tfields,
+        _ => 0,
+    };
+
+    let header = fits.hdus[0]
+        .cards
+        .iter()
+        .chain(&table.cards)
+        .filter(|card| !is_layout_keyword(card.keyword_str()))
+        .map(|card| {
+            String::from_utf8_lossy(&format_card(card))
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+
+    let columns = (1..=tfields)
+        .map(|n| {
+            let name = card_string(&table.cards, &format!("TTYPE{n}"))
+                .unwrap_or_else(|| format!("col{n}"));
+            let described = [("unit", "TUNIT"), ("ucd", "TUCD"), ("description", "TCOMM")]
+                .into_iter()
+                .filter_map(|(key, keyword)| {
+                    card_string(&table.cards, &format!("{keyword}{n}"))
+                        .filter(|value| !value.is_empty())
+                        .map(|value| (key.to_string(), value))
+                })
+                .collect::<Vec<_>>();
+            (name, described)
+        })
+        .chain(
+            card_string(&table.cards, "BUNIT")
+                .map(|unit| ("value".to_string(), vec![("unit".to_string(), unit)])),
+        )
+        .filter(|(_, described)| !described.is_empty())
+        .collect();
+
+    Ok(FitsMetadata {
+        extname: card_string(&table.cards, "EXTNAME"),
+        header,
+        columns,
+    })
+}
+
+// Resolve `--hdu` to the table or image to read. Without a selector the first
+// table is used, which is HDU 1 for the usual catalogue layout.
+fn resolve_hdu(fits: &FitsData, selector: Option<&str>) -> Result<usize> {
+    let index = match selector {
+        Some(selector) => find_hdu(fits, selector)?,
+        None => default_hdu(fits).ok_or_else(|| {
+            anyhow!(
+                "No table or image HDU found. Run `dog --list-hdus` to see what the file contains."
+            )
+        })?,
+    };
+
+    let hdu = &fits.hdus[index];
+    if is_table(hdu) || image_shape(hdu).is_some() {
+        return Ok(index);
+    }
+    match hdu.info {
+        HduInfo::Primary { .. } | HduInfo::Image { .. } => bail!("HDU {index} has no pixels."),
+        _ => bail!("HDU {index} is a {}, not a table or image.", hdu_kind(hdu)),
+    }
+}
+
+pub fn read_fits_file(path: &Path, options: &ReadOptions) -> Result<LazyFrame> {
+    let fits = open_fits(path)?;
+    let hdu = fits.hdus[resolve_hdu(&fits, options.hdu.as_deref())?].clone();
+    let bad_column = |e| anyhow!("Bad column definition: {e}");
+    let (naxis2, columns) = match hdu.info {
+        HduInfo::BinaryTable {
+            naxis2, tfields, ..
+        } => (
+            naxis2,
+            TableColumns::Binary(
+                parse_binary_table_columns(&hdu.cards, tfields).map_err(bad_column)?,
+            ),
+        ),
+        HduInfo::AsciiTable {
+            naxis2, tfields, ..
+        } => (
+            naxis2,
+            TableColumns::Ascii(
+                parse_ascii_table_columns(&hdu.cards, tfields).map_err(bad_column)?,
+            ),
+        ),
+        _ => {
+            let (bitpix, naxes) =
+                image_shape(&hdu).expect("resolve_hdu only returns tables or images");
+            if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+                bail!("Unsupported BITPIX {bitpix}.");
+            }
+            (
+                naxes.iter().product(),
+                TableColumns::Image {
+                    bitpix,
+                    naxes: naxes.to_vec(),
+                },
+            )
+        }
+    };
+    let rows = match options.tail_rows {
+        Some(n) => naxis2.saturating_sub(n)..naxis2,
+        None => 0..naxis2,
+    };
+
+    let mut scan = FitsScan {
+        path: path.to_path_buf(),
+        hdu,
+        columns,
+        explode: options.explode_vectors,
+        rows,
+        outputs: Vec::new(),
+    };
+    // Decoding no rows gives the schema, and which columns each descriptor becomes.
+    let all: Vec<usize> = (0..scan.columns.len()).collect();
+    let empty = scan.decode(&[], &[], 0..0, &all)?;
+    scan.outputs = empty
+        .iter()
+        .map(|columns| columns.iter().map(|c| c.name().clone()).collect())
+        .collect();
+    let schema = Schema::from_iter(empty.iter().flatten().map(|c| c.field().into_owned()));
+
+    Ok(LazyFrame::anonymous_scan(
+        Arc::new(scan),
+        ScanArgsAnonymous {
+            schema: Some(Arc::new(schema)),
+            name: "fits",
+            ..Default::default()
+        },
+    )?)
+}
+
+enum TableColumns {
+    Binary(Vec<BinaryColumnDescriptor>),
+    Ascii(Vec<AsciiColumnDescriptor>),
+    /// One coordinate column per axis, then the pixel value; each pixel is a row.
+    Image {
+        bitpix: i64,
+        naxes: Vec<usize>,
+    },
+}
+
+impl TableColumns {
+    fn len(&self) -> usize {
+        match self {
+            TableColumns::Binary(descriptors) => descriptors.len(),
+            TableColumns::Ascii(descriptors) => descriptors.len(),
+            TableColumns::Image { naxes, .. } => naxes.len() + 1,
+        }
+    }
+}
+
+/// A binary or ASCII table, or an image, read lazily one batch of rows at a time.
+struct FitsScan {
+    path: PathBuf,
+    hdu: Hdu,
+    columns: TableColumns,
+    explode: bool,
+    /// Rows of the table that are visible to the scan (all but the last `-t`
+    /// rows are skipped when only the tail is printed).
+    rows: Range<usize>,
+    /// Names of the polars columns each descriptor decodes into.
+    outputs: Vec<Vec<PlSmallStr>>,
+}
+
+impl FitsScan {
+    fn naxis1(&self) -> usize {
+        match (&self.columns, &self.hdu.info) {
+            (TableColumns::Image { bitpix, .. }, _) => bitpix.unsigned_abs() as usize / 8,
+            (_, HduInfo::BinaryTable { naxis1, .. } | HduInfo::AsciiTable { naxis1, .. }) => {
+                *naxis1
+            }
+            _ => unreachable!("only tables and images are scanned"),
+        }
+    }
+
+    // Decodes the raw rows numbered `row_numbers` into the polars columns of the
+    // `wanted` descriptors; one `Vec` per descriptor, as vector columns may explode.
+    fn decode(
+        &self,
+        rows: &[u8],
+        heap: &[u8],
+        row_numbers: Range<usize>,
+        wanted: &[usize],
+    ) -> Result<Vec<Vec<Column>>> {
+        let n_rows = row_numbers.len();
+        let descriptors = match &self.columns {
+            TableColumns::Binary(descriptors) => descriptors,
+            TableColumns::Image { bitpix, naxes } => {
+                return wanted
+                    .par_iter()
+                    .map(|&index| {
+                        let column = match naxes.get(index) {
+                            Some(_) => pixel_coordinates(naxes, index, row_numbers.clone()),
+                            None => pixel_values(rows, *bitpix, &self.hdu.cards),
+                        };
+                        Ok(vec![column.into()])
+                    })
+                    .collect();
+            }
+            TableColumns::Ascii(descriptors) => {
+                let naxis1 = self.naxis1();
+                return wanted
+                    .par_iter()
+                    .map(|&index| {
+                        let column = read_ascii_column(
+                            rows,
+                            naxis1,
+                            &self.hdu.cards,
+                            index,
+                            &descriptors[index],
+                        )?;
+                        Ok(vec![column.into()])
+                    })
+                    .collect();
+            }
+        };
+        let batch = Hdu {
+            info: HduInfo::BinaryTable {
+                naxis1: self.naxis1(),
+                naxis2: n_rows,
+                pcount: heap.len(),
+                tfields: descriptors.len(),
+            },
+            header_start: 0,
+            data_start: 0,
+            data_len: rows.len(),
+            cards: self.hdu.cards.clone(),
+        };
+        wanted
+            .par_iter()
+            .map(|&index| {
+                let desc = &descriptors[index];
+                // A zero repeat count is legal and means the column holds no data.
+                if desc.repeat == 0 {
+                    return Ok(Vec::new());
+                }
+                read_column(rows, heap, &batch, index, desc, self.explode)
+            })
+            .collect()
+    }
+
+    fn read_bytes(&self, file: &mut File, start: usize, len: usize) -> Result<Vec<u8>> {
+        let mut bytes = vec![0; len];
+        file.seek(SeekFrom::Start((self.hdu.data_start + start) as u64))?;
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn read(&self, projection: Option<&[PlSmallStr]>, n_rows: Option<usize>) -> Result<DataFrame> {
+        let wanted: Vec<usize> = (0..self.columns.len())
+            .filter(|&index| {
+                projection
+                    .is_none_or(|names| self.outputs[index].iter().any(|name| names.contains(name)))
+            })
+            .collect();
+
+        let mut file = File::open(&self.path)?;
+        let naxis1 = self.naxis1();
+        let variable_length = match &self.columns {
+            TableColumns::Binary(descriptors) => wanted.iter().any(|&index| {
+                matches!(
+                    descriptors[index].col_type,
+                    BinaryColumnType::VarArrayP(_) | BinaryColumnType::VarArrayQ(_)
+                )
+            }),
+            TableColumns::Ascii(_) | TableColumns::Image { .. } => false,
+        };
+        let heap = if variable_length {
+            let HduInfo::BinaryTable { naxis2, .. } = self.hdu.info else {
+                unreachable!("only binary tables are scanned")
+            };
+            let theap =
+                card_integer(&self.hdu.cards, "THEAP").map_or(naxis1 * naxis2, |v| v as usize);
+            self.read_bytes(&mut file, theap, self.hdu.data_len.saturating_sub(theap))?
+        } else {
+            Vec::new()
+        };
+
+        let start = self.rows.start;
+        let end = n_rows.map_or(self.rows.end, |n| self.rows.end.min(start + n));
+        let batch_rows = (BATCH_BYTES / naxis1.max(1)).max(1);
+        let mut frame: Option<DataFrame> = None;
+        let mut batch_start = start;
+        loop {
+            let n = batch_rows.min(end - batch_start);
+            let rows = self.read_bytes(&mut file, batch_start * naxis1, n * naxis1)?;
+            let columns = self.decode(&rows, &heap, batch_start..batch_start + n, &wanted)?;
+            let batch = DataFrame::new(n, columns.into_iter().flatten().collect())?;
+            match frame.as_mut() {
+                Some(frame) => {
+                    frame.vstack_mut_owned(batch)?;
+                }
+                None => frame = Some(batch),
+            }
+            batch_start += n;
+            if batch_start >= end {
+                break;
+            }
+        }
+
+        let frame = frame.expect("at least one batch is read");
+        Ok(match projection {
+            Some(names) => frame.select(names.iter().cloned())?,
+            None => frame,
+        })
+    }
+}
+
+impl AnonymousScan for FitsScan {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn scan(&self, scan_opts: AnonymousScanArgs) -> PolarsResult<DataFrame> {
+        self.read(scan_opts.with_columns.as_deref(), scan_opts.n_rows)
+            .map_err(|e| polars_err!(ComputeError: "{}: {e}", self.path.display()))
+    }
+
+    fn allows_projection_pushdown(&self) -> bool {
+        true
+    }
+}
+
+fn read_column(
+    bytes: &[u8],
+    heap: &[u8],
+    hdu: &Hdu,
+    index: usize,
+    desc: &BinaryColumnDescriptor,
+    explode: bool,
+) -> Result<Vec<Column>> {
+    let name = desc
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("col{}", index + 1));
+    let column_error = |e| anyhow!("Failed to read column '{name}': {e}");
+
+    let flat = match desc.col_type {
+        BinaryColumnType::VarArrayP(elem) | BinaryColumnType::VarArrayQ(elem) => {
+            return Ok(vec![read_var_length_column(
+                bytes, heap, hdu, index, desc, elem, &name,
+            )?
+            .into()]);
+        }
+        BinaryColumnType::Bit if desc.repeat <= 64 => {
+            let BinaryColumnData::Bit(rows) =
+                read_binary_column(bytes, hdu, index).map_err(column_error)?
+            else {
+                unreachable!("X columns are read as bits")
+            };
+            return Ok(vec![bit_field_series(&name, &rows, desc.repeat).into()]);
+        }
+        _ => flat_series(
+            &name,
+            read_binary_column(bytes, hdu, index).map_err(column_error)?,
+            desc.repeat,
+            &Scaling::from_cards(&hdu.cards, index),
+        ),
+    };
+
+    // Ascii repeat is the string width, so those are always one value per row.
+    if desc.repeat > 1 && desc.col_type != BinaryColumnType::Ascii {
+        if explode {
+            expand_vector_column(&name, &flat, desc.repeat)
+        } else {
+            Ok(vec![vector_series(&flat, desc)?.into()])
+        }
+    } else {
+        Ok(vec![flat.into()])
+    }
+}
+
+fn axis_name(axis: usize) -> String {
+    match axis {
+        0 => "x".to_string(),
+        1 => "y".to_string(),
+        2 => "z".to_string(),
+        _ => format!("axis{}", axis + 1),
+    }
+}
+
+// The 1-based coordinate along `axis` of each pixel in `pixels`; NAXIS1 varies
+// fastest, as the pixels are stored.
+fn pixel_coordinates(naxes: &[usize], axis: usize, pixels: Range<usize>) -> Series {
+    let stride: usize = naxes[..axis].iter().product();
+    let values: Vec<i64> = pixels
+        .map(|pixel| (pixel / stride % naxes[axis] + 1) as i64)
+        .collect();
+    Series::new(axis_name(axis).into(), values)
+}
+
+// Physical pixel values, scaled and with BLANK or NaN as null, the same way a
+// table column is read.
+fn pixel_values(bytes: &[u8], bitpix: i64, cards: &[Card]) -> Series {
+    let data = match bitpix {
+        8 => BinaryColumnData::Byte(bytes.to_vec()),
+        16 => BinaryColumnData::Short(be_values(bytes, i16::from_be_bytes)),
+        32 => BinaryColumnData::Int(be_values(bytes, i32::from_be_bytes)),
+        64 => BinaryColumnData::Long(be_values(bytes, i64::from_be_bytes)),
+        -32 => BinaryColumnData::Float(be_values(bytes, f32::from_be_bytes)),
+        -64 => BinaryColumnData::Double(be_values(bytes, f64::from_be_bytes)),
+        _ => unreachable!("BITPIX is checked before scanning"),
+    };
+    let scaling = Scaling {
+        tscal: card_number(cards, "BSCALE").unwrap_or(1.0),
+        tzero: card_number(cards, "BZERO").unwrap_or(0.0),
+        tnull: card_integer(cards, "BLANK"),
+    };
+    flat_series("value", data, 1, &scaling)
+}
+
+/// What `--summary` and `--stats` print for an image HDU.
+pub struct ImageSummary {
+    pub hdu: HduSummary,
+    pub bitpix: i64,
+    pub bscale: f64,
+    pub bzero: f64,
+    pub unit: Option<String>,
+    pub pixels: usize,
+    pub nulls: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub mean: Option<f64>,
+    pub std: Option<f64>,
+}
+
+/// Shape, scaling and pixel statistics of the HDU picked by `--hdu`, or `None`
+/// when that is a table. The pixels are streamed a batch at a time, so this
+/// works on images far larger than memory.
+pub fn image_summary(path: &Path, hdu_selector: Option<&str>) -> Result<Option<ImageSummary>> {
+    let fits = open_fits(path)?;
+    let index = resolve_hdu(&fits, hdu_selector)?;
+    let hdu = &fits.hdus[index];
+    let Some((bitpix, _)) = image_shape(hdu) else {
+        return Ok(None);
+    };
+    if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+        bail!("Unsupported BITPIX {bitpix}.");
+    }
+
+    let pixel_size = bitpix.unsigned_abs() as usize / 8;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(hdu.data_start as u64))?;
+    let mut remaining = hdu.data_len;
+    let mut batch = vec![0; BATCH_BYTES / pixel_size * pixel_size];
+    // Welford's running mean and sum of squared deviations.
+    let (mut count, mut mean, mut m2) = (0usize, 0.0, 0.0);
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    let mut nulls = 0;
+    while remaining > 0 {
+        let bytes = &mut batch[..remaining.min(BATCH_BYTES / pixel_size * pixel_size)];
+        file.read_exact(bytes)?;
+        remaining -= bytes.len();
+        let values = pixel_values(bytes, bitpix, &hdu.cards).cast(&DataType::Float64)?;
+        for value in values.f64()?.iter() {
+            let Some(value) = value else {
+                nulls += 1;
+                continue;
+            };
+            count += 1;
+            let delta = value - mean;
+            mean += delta / count as f64;
+            m2 += delta * (value - mean);
+            min = min.min(value);
+            max = max.max(value);
+        }
+    }
+
+    let valid = |x: f64| (count > 0).then_some(x);
+    Ok(Some(ImageSummary {
+        hdu: summarise(index, hdu),
+        bitpix,
+        bscale: card_number(&hdu.cards, "BSCALE").unwrap_or(1.0),
+        bzero: card_number(&hdu.cards, "BZERO").unwrap_or(0.0),
+        unit: card_string(&hdu.cards, "BUNIT"),
+        pixels: count + nulls,
+        nulls,
+        min: valid(min),
+        max: valid(max),
+        mean: valid(mean),
+        std: (count > 1).then(|| (m2 / (count - 1) as f64).sqrt()),
+    }))
+}
+
+// One column of an ASCII table, from `rows` of `naxis1` characters each.
+fn read_ascii_column(
+    rows: &[u8],
+    naxis1: usize,
+    cards: &[Card],
+    index: usize,
+    desc: &AsciiColumnDescriptor,
+) -> Result<Series> {
+    let n = index + 1;
+    let name = desc.name.clone().unwrap_or_else(|| format!("col{n}"));
+    let tnull = cards
+        .iter()
+        .find(|card| card.keyword_str() == format!("TNULL{n}"))
+        .and_then(|card| card.value.as_ref())
+        .map(value_text);
+    let (tscal, tzero) = extract_column_scaling(cards, n);
+    let scaled = tscal != 1.0 || tzero != 0.0;
+
+    let width = desc.format.width();
+    if desc.tbcol + width > naxis1 {
+        bail!("Column '{name}' runs past the end of the row.");
+    }
+    // Each field as written, or None when it is blank or matches TNULLn.
+    let fields = rows.chunks_exact(naxis1.max(1)).map(|row| {
+        let field = String::from_utf8_lossy(&row[desc.tbcol..desc.tbcol + width]);
+        let text = field.trim();
+        let is_null = tnull.as_deref().is_some_and(|tnull| tnull.trim() == text);
+        (!is_null).then(|| field.trim_end().to_string())
+    });
+
+    let tform = cards
+        .iter()
+        .find(|card| card.keyword_str() == format!("TFORM{n}"))
+        .and_then(|card| card.value.as_ref())
+        .map(value_text)
+        .unwrap_or_default();
+    let parse_error =
+        |text: &str| anyhow!("Column '{name}' ({tform}): can't read '{text}' as a number.");
+    let name: PlSmallStr = name.as_str().into();
+    Ok(match desc.format {
+        AsciiColumnFormat::Character(_) => Series::new(name, fields.collect::<Vec<_>>()),
+        AsciiColumnFormat::Integer(_) if !scaled => {
+            let values = fields
+                .map(|field| {
+                    let Some(text) = field.as_deref().map(str::trim).filter(|t| !t.is_empty())
+                    else {
+                        return Ok(None);
+                    };
+                    let digits = text.strip_prefix('+').unwrap_or(text);
+                    digits
+                        .parse::<i64>()
+                        .map(Some)
+                        .map_err(|_| parse_error(text))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Series::new(name, values)
+        }
+        AsciiColumnFormat::Integer(_) => {
+            fortran_floats(name, fields, 0, tscal, tzero, parse_error)?
+        }
+        AsciiColumnFormat::FloatF(_, decimals)
+        | AsciiColumnFormat::FloatE(_, decimals)
+        | AsciiColumnFormat::DoubleE(_, decimals) => {
+            fortran_floats(name, fields, decimals, tscal, tzero, parse_error)?
+        }
+    })
+}
+
+fn fortran_floats(
+    name: PlSmallStr,
+    fields: impl Iterator<Item = Option<String>>,
+    decimals: usize,
+    tscal: f64,
+    tzero: f64,
+    parse_error: impl Fn(&str) -> anyhow::Error,
+) -> Result<Series> {
+    let values = fields
+        .map(|field| {
+            let Some(text) = field.as_deref().map(str::trim).filter(|t| !t.is_empty()) else {
+                return Ok(None);
+            };
+            let value = parse_fortran_float(text, decimals).ok_or_else(|| parse_error(text))?;
+            Ok(Some(tzero + tscal * value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Series::new(name, values))
+}
+
+// Reads a number as a Fortran `Fw.d`/`Ew.d`/`Dw.d` edit descriptor would: `D`
+// exponents, exponents with only a sign (`1.5-3`), and, when there is no
+// decimal point, an implied one `decimals` digits from the right.
+fn parse_fortran_float(text: &str, decimals: usize) -> Option<f64> {
+    let text: String = text
+        .chars()
+        .filter(|c| *c != ' ')
+        .map(|c| match c {
+            'D' | 'd' => 'E',
+            c => c,
+        })
+        .collect();
+    let split = text
+        .char_indices()
+        .skip(1)
+        .find(|&(i, c)| {
+            c == 'E' || c == 'e' || ((c == '+' || c == '-') && !text[..i].ends_with(['E', 'e']))
+        })
+        .map_or(text.len(), |(i, _)| i);
+    let (mantissa, exponent) = text.split_at(split);
+    let exponent: i32 = match exponent.trim_start_matches(['E', 'e']) {
+        "" => 0,
+        digits => digits.parse().ok()?,
+    };
+    let mut value: f64 = mantissa.parse().ok()?;
+    if !mantissa.contains('.') {
+        value /= 10f64.powi(decimals as i32);
+    }
+    Some(value * 10f64.powi(exponent))
+}
+
+/// The `TSCALn`, `TZEROn` and `TNULLn` keywords of one column.
+struct Scaling {
+    tscal: f64,
+    tzero: f64,
+    tnull: Option<i64>,
+}
+
+impl Scaling {
+    fn from_cards(cards: &[Card], index: usize) -> Self {
+        let (tscal, tzero) = extract_column_scaling(cards, index + 1);
+        Self {
+            tscal,
+            tzero,
+            tnull: card_integer(cards, &format!("TNULL{}", index + 1)),
+        }
+    }
+
+    fn is_offset(&self, tzero: f64) -> bool {
+        self.tscal == 1.0 && self.tzero == tzero
+    }
+}
+
+// All cell values of a fixed-width column, flattened row by row.
+fn flat_series(name: &str, data: BinaryColumnData, repeat: usize, scaling: &Scaling) -> Series {
+    let name: PlSmallStr = name.into();
+    match data {
+        BinaryColumnData::Logical(v) => Series::new(name, v),
+        BinaryColumnData::Byte(v) => {
+            scaled_integers(name, v, scaling, -128.0, |x| (x ^ 0x80) as i8)
+        }
+        BinaryColumnData::Short(v) => {
+            scaled_integers(name, v, scaling, 32768.0, |x| x as u16 ^ 0x8000)
+        }
+        BinaryColumnData::Int(v) => {
+            scaled_integers(name, v, scaling, 2147483648.0, |x| x as u32 ^ 0x8000_0000)
+        }
+        BinaryColumnData::Long(v) => {
+            scaled_integers(name, v, scaling, 9223372036854775808.0, |x| {
+                x as u64 ^ (1 << 63)
+            })
+        }
+        BinaryColumnData::Float(v) if scaling.is_offset(0.0) => Series::new(
+            name,
+            v.into_iter()
+                .map(|x| (!x.is_nan()).then_some(x))
+                .collect::<Vec<_>>(),
+        ),
+        BinaryColumnData::Float(v) => scaled_floats(name, v.into_iter().map(f64::from), scaling),
+        BinaryColumnData::Double(v) => scaled_floats(name, v.into_iter(), scaling),
+        BinaryColumnData::ComplexFloat(v) => {
+            let (re, im): (Vec<f32>, Vec<f32>) = v.into_iter().unzip();
+            complex_series(
+                name,
+                Series::new("re".into(), re),
+                Series::new("im".into(), im),
+            )
+        }
+        BinaryColumnData::ComplexDouble(v) => {
+            let (re, im): (Vec<f64>, Vec<f64>) = v.into_iter().unzip();
+            complex_series(
+                name,
+                Series::new("re".into(), re),
+                Series::new("im".into(), im),
+            )
+        }
+        BinaryColumnData::Ascii(v) => Series::new(name, v),
+        BinaryColumnData::Bit(rows) => Series::new(
+            name,
+            rows.iter()
+                .flat_map(|row| unpack_bits(row, repeat))
+                .collect::<Vec<bool>>(),
+        ),
+        BinaryColumnData::VarByte(_)
+        | BinaryColumnData::VarShort(_)
+        | BinaryColumnData::VarInt(_)
+        | BinaryColumnData::VarLong(_)
+        | BinaryColumnData::VarFloat(_)
+        | BinaryColumnData::VarDouble(_) => {
+            unreachable!("variable-length columns are read separately")
+        }
+    }
+}
+
+// Stored integers are kept as they are, flipped into the signed or unsigned type
+// when TZERO is the standard offset for it (`unsigned_zero`), and scaled to
+// Float64 otherwise. TNULL is compared against the stored value.
+fn scaled_integers<T, U>(
+    name: PlSmallStr,
+    raw: Vec<T>,
+    scaling: &Scaling,
+    unsigned_zero: f64,
+    flip: fn(T) -> U,
+) -> Series
+where
+    T: Copy + Into<i64>,
+    Series: NamedFrom<Vec<Option<T>>, [Option<T>]> + NamedFrom<Vec<Option<U>>, [Option<U>]>,
+{
+    let tnull = scaling.tnull;
+    if scaling.is_offset(0.0) {
+        Series::new(name, non_null(&raw, tnull, |x| x))
+    } else if scaling.is_offset(unsigned_zero) {
+        Series::new(name, non_null(&raw, tnull, flip))
+    } else {
+        let (tscal, tzero) = (scaling.tscal, scaling.tzero);
+        Series::new(
+            name,
+            non_null(&raw, tnull, |x| tzero + tscal * x.into() as f64),
+        )
+    }
+}
+
+fn non_null<T: Copy + Into<i64>, V>(
+    raw: &[T],
+    tnull: Option<i64>,
+    convert: impl Fn(T) -> V,
+) -> Vec<Option<V>> {
+    raw.iter()
+        .map(|&x| (tnull != Some(x.into())).then(|| convert(x)))
+        .collect()
+}
+
+fn scaled_floats(name: PlSmallStr, raw: impl Iterator<Item = f64>, scaling: &Scaling) -> Series {
+    let values = raw.map(|x| (!x.is_nan()).then_some(scaling.tzero + scaling.tscal * x));
+    Series::new(name, values.collect::<Vec<_>>())
+}
+
+fn complex_series(name: PlSmallStr, re: Series, im: Series) -> Series {
+    StructChunked::from_series(name, re.len(), [re, im].iter())
+        .expect("re and im have the same length")
+        .into_series()
+}
+
+// FITS packs bits most significant first; trailing pad bits are dropped.
+fn unpack_bits(bytes: &[u8], count: usize) -> Vec<bool> {
+    (0..count)
+        .map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+        .collect()
+}
+
+// `1X` is a Boolean. Up to `64X` the bits are read as one unsigned integer, with
+// the first bit as the most significant, so flag columns keep their usual values.
+fn bit_field_series(name: &str, rows: &[Vec<u8>], repeat: usize) -> Series {
+    let name: PlSmallStr = name.into();
+    if repeat == 1 {
+        return Series::new(
+            name,
+            rows.iter()
+                .map(|row| row[0] & 0x80 != 0)
+                .collect::<Vec<_>>(),
+        );
+    }
+    let values = rows.iter().map(|row| {
+        let packed = row.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        packed >> (row.len() * 8 - repeat)
+    });
+    match repeat {
+        2..=8 => Series::new(name, values.map(|v| v as u8).collect::<Vec<_>>()),
+        9..=16 => Series::new(name, values.map(|v| v as u16).collect::<Vec<_>>()),
+        17..=32 => Series::new(name, values.map(|v| v as u32).collect::<Vec<_>>()),
+        _ => Series::new(name, values.collect::<Vec<_>>()),
+    }
+}
+
+// One Array per row, nested to the TDIMn shape when it matches the repeat count.
+// TDIMn lists the fastest-varying axis first, so the polars shape is reversed.
+fn vector_series(flat: &Series, desc: &BinaryColumnDescriptor) -> Result<Series> {
+    let axes = match &desc.tdim {
+        Some(tdim) if tdim.iter().product::<usize>() == desc.repeat => {
+            tdim.iter().rev().copied().collect()
+        }
+        _ => vec![desc.repeat],
+    };
+    let shape: Vec<ReshapeDimension> = std::iter::once(flat.len() / desc.repeat)
+        .chain(axes)
+        .map(|n| ReshapeDimension::new(n as i64))
+        .collect();
+    Ok(flat.reshape_array(&shape)?)
+}
+
+// Splits a flattened vector column into `name_0..name_N` scalar columns.
+fn expand_vector_column(name: &str, flat: &Series, repeat: usize) -> Result<Vec<Column>> {
+    (0..repeat)
+        .map(|i| {
+            Ok(flat
+                .gather_every(repeat, i)?
+                .with_name(format!("{name}_{i}").into())
+                .into())
+        })
+        .collect()
+}
+
+// Reads a P/Q descriptor column. Each row holds (count, offset) into the heap,
+// which starts THEAP bytes into the data unit (directly after the table by default).
+fn read_var_length_column(
+    bytes: &[u8],
+    heap: &[u8],
+    hdu: &Hdu,
+    index: usize,
+    desc: &BinaryColumnDescriptor,
+    elem: char,
+    name: &str,
+) -> Result<Series> {
+    let HduInfo::BinaryTable {
+        naxis1,
+        naxis2,
+        tfields,
+        ..
+    } = hdu.info
+    else {
+        bail!("not a binary table HDU");
+    };
+    let descriptors = parse_binary_table_columns(&hdu.cards, tfields)
+        .map_err(|e| anyhow!("Bad column definition: {e}"))?;
+    let column_offset: usize = descriptors[..index].iter().map(|d| d.byte_width).sum();
+    let is_q = matches!(desc.col_type, BinaryColumnType::VarArrayQ(_));
+
+    let element_bytes = |count: usize| match elem {
+        'X' => count.div_ceil(8),
+        'L' | 'B' | 'A' => count,
+        'I' => count * 2,
+        'J' | 'E' => count * 4,
+        'K' | 'D' | 'C' => count * 8,
+        'M' => count * 16,
+        _ => unreachable!("TFORM element codes are validated when parsed"),
+    };
+
+    let mut cells: Vec<(&[u8], usize)> = Vec::with_capacity(naxis2);
+    for row in 0..naxis2 {
+        let at = hdu.data_start + row * naxis1 + column_offset;
+        let (count, offset) = if is_q {
+            let word = |i: usize| u64::from_be_bytes(bytes[at + i..at + i + 8].try_into().unwrap());
+            (word(0) as usize, word(8) as usize)
+        } else {
+            let word = |i: usize| u32::from_be_bytes(bytes[at + i..at + i + 4].try_into().unwrap());
+            (word(0) as usize, word(4) as usize)
+        };
+        let end = offset + element_bytes(count);
+        if end > heap.len() {
+            bail!("Column '{name}' points past the end of the heap in row {row}.");
+        }
+        cells.push((&heap[offset..end], count));
+    }
+
+    if elem == 'A' {
+        let strings = cells.iter().map(|(cell, _)| {
+            String::from_utf8_lossy(cell)
+                .trim_end_matches(['\0', ' '])
+                .to_string()
+        });
+        return Ok(Series::new(name.into(), strings.collect::<Vec<_>>()));
+    }
+
+    // Array elements are scaled, and TNULL or NaN made null, like fixed-width cells.
+    let scaling = Scaling::from_cards(&hdu.cards, index);
+    let decode = |cell: &[u8], count: usize| {
+        let empty = PlSmallStr::EMPTY;
+        let numbers = |data| flat_series("", data, 1, &scaling);
+        match elem {
+            'L' => Series::new(empty, cell.iter().map(|&b| b == b'T').collect::<Vec<_>>()),
+            'X' => Series::new(empty, unpack_bits(cell, count)),
+            'B' => numbers(BinaryColumnData::Byte(cell.to_vec())),
+            'I' => numbers(BinaryColumnData::Short(be_values(cell, i16::from_be_bytes))),
+            'J' => numbers(BinaryColumnData::Int(be_values(cell, i32::from_be_bytes))),
+            'K' => numbers(BinaryColumnData::Long(be_values(cell, i64::from_be_bytes))),
+            'E' => numbers(BinaryColumnData::Float(be_values(cell, f32::from_be_bytes))),
+            'D' => numbers(BinaryColumnData::Double(be_values(cell, f64::from_be_bytes))),
+            'C' => {
+                let parts = be_values(cell, f32::from_be_bytes);
+                let re: Vec<f32> = parts.iter().step_by(2).copied().collect();
+                let im: Vec<f32> = parts.iter().skip(1).step_by(2).copied().collect();
+                complex_series(
+                    empty,
+                    Series::new("re".into(), re),
+                    Series::new("im".into(), im),
+                )
+            }
+            'M' => {
+                let parts = be_values(cell, f64::from_be_bytes);
+                let re: Vec<f64> = parts.iter().step_by(2).copied().collect();
+                let im: Vec<f64> = parts.iter().skip(1).step_by(2).copied().collect();
+                complex_series(
+                    empty,
+                    Series::new("re".into(), re),
+                    Series::new("im".into(), im),
+                )
+            }
+            _ => unreachable!("TFORM element codes are validated when parsed"),
+        }
+    };
+    if cells.is_empty() {
+        // Without any rows polars can't infer the element type.
+        let inner = decode(&[], 0).dtype().clone();
+        return Ok(Series::new_empty(
+            name.into(),
+            &DataType::List(Box::new(inner)),
+        ));
+    }
+    let rows: Vec<Series> = cells
+        .iter()
+        .map(|&(cell, count)| decode(cell, count))
+        .collect();
+    Ok(Series::new(name.into(), rows))
+}
+
+fn be_values<T, const N: usize>(cell: &[u8], from_be_bytes: fn([u8; N]) -> T) -> Vec<T> {
+    cell.chunks_exact(N)
+        .map(|chunk| from_be_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
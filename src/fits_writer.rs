@@ -0,0 +1,615 @@
+//! Writing tables as FITS: a data-less primary HDU followed by one BINTABLE.
+//!
+//! Columns are stored with the TFORM codes `fits.rs` reads back, so a table
+//! survives a round trip through FITS with the same dtypes:
+//!
+//! | dtype | TFORM |
+//! | --- | --- |
+//! | Boolean | `L` |
+//! | UInt8, Int16, Int32, Int64 | `B`, `I`, `J`, `K` |
+//! | Int8, UInt16, UInt32, UInt64 | `B`, `I`, `J`, `K` with the standard `TZEROn` offset |
+//! | Float32, Float64 | `E`, `D` |
+//! | Struct of `re`/`im` floats | `C` or `M` |
+//! | String | `nA`, as wide as the longest value |
+//! | Array | the element code with a repeat count, plus `TDIMn` when nested |
+//! | List | as Array when every list has the same length, otherwise `1P` |
+//!
+//! Dates, times, decimals and categoricals are written as strings or Float64.
+//! Nulls become NaN in float columns, blanks in strings, and in integer
+//! columns the smallest value of the stored type (largest for unsigned ones),
+//! declared with `TNULLn`.
+
+use anyhow::{bail, Context, Result};
+use fitsio_pure_tsl::header::{parse_card, serialize_header, Card};
+use fitsio_pure_tsl::primary::build_primary_header;
+use fitsio_pure_tsl::value::Value;
+use polars::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::compression::OutputFile;
+use crate::fits::{is_layout_keyword, FitsMetadata};
+
+const BLOCK_SIZE: usize = 2880;
+/// The TZERO of unsigned 64-bit columns, 2^63, which `Value::Integer` can't hold.
+const UINT64_TZERO: u64 = 1 << 63;
+/// TTYPEn and the other column keywords leave room for three digits.
+const MAX_COLUMNS: usize = 999;
+/// The longest string a card's value field holds, with quotes doubled.
+const MAX_STRING: usize = 68;
+
+/// How one element of a column is stored.
+struct Leaf {
+    code: char,
+    tzero: Option<Value>,
+    /// The stored value that stands for null, for integer codes.
+    sentinel: Option<i64>,
+    has_nulls: bool,
+    /// Whether a non-null value equals the sentinel, so nulls can't be marked.
+    uses_sentinel: bool,
+}
+
+impl Leaf {
+    fn size(&self) -> usize {
+        match self.code {
+            'L' | 'B' | 'A' => 1,
+            'I' => 2,
+            'J' | 'E' => 4,
+            'K' | 'D' | 'C' => 8,
+            'M' => 16,
+            _ => unreachable!("only the codes above are written"),
+        }
+    }
+}
+
+enum Cells {
+    /// Every row's bytes, back to back.
+    Fixed(Vec<u8>),
+    /// Element count and heap bytes of every row of a `P` column.
+    Variable(Vec<(usize, Vec<u8>)>),
+}
+
+struct EncodedColumn {
+    name: String,
+    leaf: Leaf,
+    repeat: usize,
+    /// TDIM in FITS order, fastest-varying axis first.
+    tdim: Option<Vec<usize>>,
+    cells: Cells,
+}
+
+impl EncodedColumn {
+    fn width(&self) -> usize {
+        match self.cells {
+            Cells::Fixed(_) => self.repeat * self.leaf.size(),
+            Cells::Variable(_) => 8,
+        }
+    }
+
+    fn tform(&self) -> String {
+        match &self.cells {
+            Cells::Fixed(_) => format!("{}{}", self.repeat, self.leaf.code),
+            Cells::Variable(cells) => {
+                let longest = cells.iter().map(|(count, _)| *count).max().unwrap_or(0);
+                format!("1P{}({longest})", self.leaf.code)
+            }
+        }
+    }
+}
+
+/// Writes `lazy_frame` to `output_path` as a FITS binary table. The cards in
+/// `metadata.header` are copied into the table header, and each column's
+/// unit, UCD and description become its TUNITn, TUCDn and TCOMMn.
+pub fn write_fits(
+    lazy_frame: &LazyFrame,
+    output_path: &Path,
+    metadata: &FitsMetadata,
+) -> Result<()> {
+    let df = lazy_frame.clone().collect()?;
+    if df.width() > MAX_COLUMNS {
+        bail!(
+            "FITS tables hold at most {MAX_COLUMNS} columns, and this one has {}; select fewer with -c.",
+            df.width()
+        );
+    }
+    for name in df.get_column_names() {
+        if name.len() + name.matches('\'').count() > MAX_STRING {
+            bail!("Column name '{name}' is too long for a FITS TTYPE card, which holds {MAX_STRING} characters.");
+        }
+    }
+    let columns = df
+        .columns()
+        .iter()
+        .map(|column| encode_column(column.as_materialized_series()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let rows = df.height();
+    let naxis1: usize = columns.iter().map(EncodedColumn::width).sum();
+    let mut data = vec![0; naxis1 * rows];
+    let mut heap = Vec::new();
+    let mut offset = 0;
+    for column in &columns {
+        let width = column.width();
+        match &column.cells {
+            Cells::Fixed(bytes) => {
+                for (row, cell) in bytes.chunks_exact(width.max(1)).enumerate().take(rows) {
+                    data[row * naxis1 + offset..][..width].copy_from_slice(cell);
+                }
+            }
+            Cells::Variable(cells) => {
+                for (row, (count, bytes)) in cells.iter().enumerate() {
+                    let at = i32::try_from(heap.len())
+                        .context("The variable-length columns are too large for a FITS heap.")?;
+                    heap.extend_from_slice(bytes);
+                    let descriptor = [(*count as i32).to_be_bytes(), at.to_be_bytes()].concat();
+                    data[row * naxis1 + offset..][..8].copy_from_slice(&descriptor);
+                }
+            }
+        }
+        offset += width;
+    }
+
+    let mut primary = build_primary_header(8, &[])?;
+    primary.push(card("EXTEND", Value::Logical(true)));
+    let table = table_header(&columns, metadata, naxis1, rows, heap.len())?;
+
+    let mut table_bytes = serialize_header(&table)?;
+    write_exact_uint64_tzero(&table, &mut table_bytes);
+
+    let mut file = OutputFile::create(output_path)?;
+    file.write_all(&serialize_header(&primary)?)?;
+    file.write_all(&table_bytes)?;
+    data.extend_from_slice(&heap);
+    data.resize(data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+    file.write_all(&data)?;
+    file.finish()?;
+    Ok(())
+}
+
+fn card(keyword: &str, value: Value) -> Card {
+    let mut padded = [b' '; 8];
+    padded[..keyword.len()].copy_from_slice(keyword.as_bytes());
+    Card {
+        keyword: padded,
+        value: Some(value),
+        comment: None,
+    }
+}
+
+// The TZERO of an unsigned 64-bit column goes through the header as a float,
+// which would be written rounded, so its value field is rewritten in the
+// serialized header as the exact integer.
+fn write_exact_uint64_tzero(cards: &[Card], header: &mut [u8]) {
+    let digits = UINT64_TZERO.to_string();
+    for (card, image) in cards.iter().zip(header.chunks_exact_mut(80)) {
+        if card.keyword_str().starts_with("TZERO") && card.value == Some(Value::Float(UINT64_TZERO as f64)) {
+            image[10..30].fill(b' ');
+            image[30 - digits.len()..30].copy_from_slice(digits.as_bytes());
+        }
+    }
+}
+
+// Parses a card image that had its trailing blanks trimmed.
+fn card_from_line(line: &str) -> Option<Card> {
+    let mut image = [b' '; 80];
+    let len = line.len().min(80);
+    image[..len].copy_from_slice(&line.as_bytes()[..len]);
+    parse_card(&image).ok()
+}
+
+fn table_header(
+    columns: &[EncodedColumn],
+    metadata: &FitsMetadata,
+    naxis1: usize,
+    naxis2: usize,
+    pcount: usize,
+) -> Result<Vec<Card>> {
+    let integer = |n: usize| Value::Integer(n as i64);
+    let mut cards = vec![
+        card("XTENSION", Value::String("BINTABLE".into())),
+        card("BITPIX", Value::Integer(8)),
+        card("NAXIS", Value::Integer(2)),
+        card("NAXIS1", integer(naxis1)),
+        card("NAXIS2", integer(naxis2)),
+        card("PCOUNT", integer(pcount)),
+        card("GCOUNT", Value::Integer(1)),
+        card("TFIELDS", integer(columns.len())),
+    ];
+    for (i, column) in columns.iter().enumerate() {
+        let n = i + 1;
+        cards.push(card(
+            &format!("TTYPE{n}"),
+            Value::String(column.name.clone()),
+        ));
+        cards.push(card(&format!("TFORM{n}"), Value::String(column.tform())));
+        let described = metadata.column(&column.name).unwrap_or_default();
+        let describe = |key: &str| {
+            described
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| Value::String(value.clone()))
+        };
+        if let Some(unit) = describe("unit") {
+            cards.push(card(&format!("TUNIT{n}"), unit));
+        }
+        if column.leaf.has_nulls {
+            if let Some(sentinel) = column.leaf.sentinel {
+                cards.push(card(&format!("TNULL{n}"), Value::Integer(sentinel)));
+            }
+        }
+        if let Some(tzero) = &column.leaf.tzero {
+            cards.push(card(&format!("TZERO{n}"), tzero.clone()));
+        }
+        if let Some(tdim) = &column.tdim {
+            let axes: Vec<String> = tdim.iter().map(|n| n.to_string()).collect();
+            cards.push(card(
+                &format!("TDIM{n}"),
+                Value::String(format!("({})", axes.join(","))),
+            ));
+        }
+        if let Some(ucd) = describe("ucd") {
+            cards.push(card(&format!("TUCD{n}"), ucd));
+        }
+        if let Some(description) = describe("description") {
+            cards.push(card(&format!("TCOMM{n}"), description));
+        }
+    }
+
+    let mut carried: Vec<Card> = Vec::new();
+    for line in &metadata.header {
+        let Some(header_card) = card_from_line(line) else {
+            continue;
+        };
+        let keyword = header_card.keyword_str().to_string();
+        if keyword.is_empty() || is_layout_keyword(&keyword) {
+            continue;
+        }
+        // The primary and table headers can both set a keyword; the table's,
+        // which comes last, wins.
+        if !header_card.is_commentary() {
+            carried.retain(|c| c.keyword_str() != keyword);
+        }
+        carried.push(header_card);
+    }
+    cards.extend(carried);
+    Ok(cards)
+}
+
+// The dtype a column is written as, for the dtypes FITS has no code for.
+pub(crate) fn storable_dtype(dtype: &DataType) -> DataType {
+    match dtype {
+        DataType::List(inner) => DataType::List(Box::new(storable_dtype(inner))),
+        DataType::Array(inner, width) => DataType::Array(Box::new(storable_dtype(inner)), *width),
+        DataType::Decimal(..) | DataType::Null => DataType::Float64,
+        DataType::Date
+        | DataType::Datetime(..)
+        | DataType::Duration(_)
+        | DataType::Time
+        | DataType::Categorical(..)
+        | DataType::Enum(..) => DataType::String,
+        other => other.clone(),
+    }
+}
+
+fn encode_column(series: &Series) -> Result<EncodedColumn> {
+    let series = series.cast(&storable_dtype(series.dtype()))?;
+    let name = series.name().to_string();
+    let column = |leaf, repeat, tdim, cells| EncodedColumn {
+        name: name.clone(),
+        leaf,
+        repeat,
+        tdim,
+        cells,
+    };
+
+    match series.dtype() {
+        DataType::String => {
+            let strings = series.str()?;
+            let width = strings
+                .iter()
+                .map(|s| s.map_or(0, str::len))
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            let mut bytes = Vec::with_capacity(width * strings.len());
+            for value in strings.iter() {
+                let value = value.unwrap_or_default().as_bytes();
+                bytes.extend_from_slice(value);
+                bytes.resize(bytes.len() + width - value.len(), b' ');
+            }
+            let leaf = Leaf {
+                code: 'A',
+                tzero: None,
+                sentinel: None,
+                has_nulls: false,
+                uses_sentinel: false,
+            };
+            Ok(column(leaf, width, None, Cells::Fixed(bytes)))
+        }
+        DataType::List(inner) => {
+            let lists = series.list()?;
+            let mut lengths = lists
+                .amortized_iter()
+                .flatten()
+                .map(|cell| cell.as_ref().len());
+            let first = lengths.next();
+            if let Some(width) = first.filter(|&n| n > 0 && lengths.all(|len| len == n)) {
+                let fixed = series.cast(&DataType::Array(inner.clone(), width))?;
+                return encode_column(&fixed);
+            }
+
+            let mut cells = Vec::with_capacity(lists.len());
+            let mut leaf = encode_leaf(
+                &Series::new_empty(PlSmallStr::EMPTY, inner),
+                &mut Vec::new(),
+            )
+            .with_context(|| format!("Column '{name}' can't be written to FITS."))?;
+            for cell in lists.amortized_iter() {
+                let Some(cell) = cell else {
+                    cells.push((0, Vec::new()));
+                    continue;
+                };
+                let mut bytes = Vec::new();
+                let cell_leaf = encode_leaf(cell.as_ref(), &mut bytes)?;
+                leaf.has_nulls |= cell_leaf.has_nulls;
+                leaf.uses_sentinel |= cell_leaf.uses_sentinel;
+                cells.push((cell.as_ref().len(), bytes));
+            }
+            check_sentinel(&name, &leaf)?;
+            Ok(column(leaf, 1, None, Cells::Variable(cells)))
+        }
+        DataType::Array(..) => {
+            let (values, shape) = flatten_array(&series)?;
+            let mut bytes = Vec::new();
+            let leaf = encode_leaf(&values, &mut bytes)
+                .with_context(|| format!("Column '{name}' can't be written to FITS."))?;
+            check_sentinel(&name, &leaf)?;
+            // Polars lists the slowest-varying axis first, TDIM the fastest.
+            let tdim = (shape.len() > 1).then(|| shape.iter().rev().copied().collect());
+            Ok(column(
+                leaf,
+                shape.iter().product(),
+                tdim,
+                Cells::Fixed(bytes),
+            ))
+        }
+        _ => {
+            let mut bytes = Vec::new();
+            let leaf = encode_leaf(&series, &mut bytes)
+                .with_context(|| format!("Column '{name}' can't be written to FITS."))?;
+            check_sentinel(&name, &leaf)?;
+            Ok(column(leaf, 1, None, Cells::Fixed(bytes)))
+        }
+    }
+}
+
+fn check_sentinel(name: &str, leaf: &Leaf) -> Result<()> {
+    if let (true, true, Some(sentinel)) = (leaf.has_nulls, leaf.uses_sentinel, leaf.sentinel) {
+        bail!(
+            "Column '{name}' has nulls, but also holds the value stored as {sentinel}, which FITS would use (as TNULL) to mark them."
+        );
+    }
+    Ok(())
+}
+
+// The elements of a (possibly nested) Array column, with null rows turned into
+// null elements, and the array shape.
+fn flatten_array(series: &Series) -> Result<(Series, Vec<usize>)> {
+    let mut values = series.rechunk();
+    let mut shape = Vec::new();
+    while let DataType::Array(_, width) = values.dtype() {
+        let width = *width;
+        let arrays = values.array()?;
+        let mut inner = arrays.get_inner();
+        if arrays.null_count() > 0 {
+            let mask: BooleanChunked = arrays
+                .is_not_null()
+                .iter()
+                .map(|valid| valid.unwrap_or(false))
+                .flat_map(|valid| std::iter::repeat_n(valid, width))
+                .collect();
+            let nulls = Series::full_null(inner.name().clone(), inner.len(), inner.dtype());
+            inner = inner.zip_with(&mask, &nulls)?;
+        }
+        shape.push(width);
+        values = inner;
+    }
+    Ok((values, shape))
+}
+
+// Appends the big-endian bytes of every value to `out`.
+fn encode_leaf(values: &Series, out: &mut Vec<u8>) -> Result<Leaf> {
+    let leaf = |code, tzero, sentinel, (has_nulls, uses_sentinel)| Leaf {
+        code,
+        tzero,
+        sentinel,
+        has_nulls,
+        uses_sentinel,
+    };
+    Ok(match values.dtype() {
+        DataType::Boolean => {
+            out.extend(values.bool()?.iter().map(|v| match v {
+                Some(true) => b'T',
+                Some(false) => b'F',
+                None => 0,
+            }));
+            leaf('L', None, None, (values.null_count() > 0, false))
+        }
+        DataType::UInt8 => {
+            let nulls = integers(values.u8()?, u8::MAX, |v| [v], out);
+            leaf('B', None, Some(u8::MAX.into()), nulls)
+        }
+        DataType::Int8 => {
+            let nulls = integers(values.i8()?, i8::MIN, |v| [v as u8 ^ 0x80], out);
+            leaf('B', Some(Value::Integer(-128)), Some(0), nulls)
+        }
+        DataType::Int16 => {
+            let nulls = integers(values.i16()?, i16::MIN, i16::to_be_bytes, out);
+            leaf('I', None, Some(i16::MIN.into()), nulls)
+        }
+        DataType::UInt16 => {
+            let raw = |v: u16| ((v ^ 0x8000) as i16).to_be_bytes();
+            let nulls = integers(values.u16()?, u16::MAX, raw, out);
+            leaf(
+                'I',
+                Some(Value::Integer(32768)),
+                Some(i16::MAX.into()),
+                nulls,
+            )
+        }
+        DataType::Int32 => {
+            let nulls = integers(values.i32()?, i32::MIN, i32::to_be_bytes, out);
+            leaf('J', None, Some(i32::MIN.into()), nulls)
+        }
+        DataType::UInt32 => {
+            let raw = |v: u32| ((v ^ 0x8000_0000) as i32).to_be_bytes();
+            let nulls = integers(values.u32()?, u32::MAX, raw, out);
+            leaf(
+                'J',
+                Some(Value::Integer(2147483648)),
+                Some(i32::MAX.into()),
+                nulls,
+            )
+        }
+        DataType::Int64 => {
+            let nulls = integers(values.i64()?, i64::MIN, i64::to_be_bytes, out);
+            leaf('K', None, Some(i64::MIN), nulls)
+        }
+        DataType::UInt64 => {
+            let raw = |v: u64| ((v ^ (1 << 63)) as i64).to_be_bytes();
+            let nulls = integers(values.u64()?, u64::MAX, raw, out);
+            leaf(
+                'K',
+                Some(Value::Float(UINT64_TZERO as f64)),
+                Some(i64::MAX),
+                nulls,
+            )
+        }
+        DataType::Float32 => {
+            for v in values.f32()?.iter() {
+                out.extend_from_slice(&v.unwrap_or(f32::NAN).to_be_bytes());
+            }
+            leaf('E', None, None, (values.null_count() > 0, false))
+        }
+        DataType::Float64 => {
+            for v in values.f64()?.iter() {
+                out.extend_from_slice(&v.unwrap_or(f64::NAN).to_be_bytes());
+            }
+            leaf('D', None, None, (values.null_count() > 0, false))
+        }
+        DataType::Struct(fields)
+            if fields.len() == 2
+                && fields[0].name() == "re"
+                && fields[1].name() == "im"
+                && fields.iter().all(|f| f.dtype().is_float()) =>
+        {
+            let parts = values.struct_()?;
+            let valid = parts.is_not_null();
+            let single = fields.iter().all(|f| f.dtype() == &DataType::Float32);
+            let part = |name: &str| -> Result<Vec<Option<f64>>> {
+                let part = parts.field_by_name(name)?.cast(&DataType::Float64)?;
+                Ok(part
+                    .f64()?
+                    .iter()
+                    .zip(valid.iter())
+                    .map(|(v, valid)| v.filter(|_| valid == Some(true)))
+                    .collect())
+            };
+            let has_nulls = values.null_count() > 0;
+            for (re, im) in part("re")?.into_iter().zip(part("im")?) {
+                for v in [re, im] {
+                    let v = v.unwrap_or(f64::NAN);
+                    if single {
+                        out.extend_from_slice(&(v as f32).to_be_bytes());
+                    } else {
+                        out.extend_from_slice(&v.to_be_bytes());
+                    }
+                }
+            }
+            leaf(
+                if single { 'C' } else { 'M' },
+                None,
+                None,
+                (has_nulls, false),
+            )
+        }
+        other => bail!("FITS tables have no type for {other}."),
+    })
+}
+
+// Writes `values` with nulls replaced by `null`; returns whether there were
+// nulls and whether `null` also appears as a real value.
+fn integers<T, const N: usize>(
+    values: &ChunkedArray<T>,
+    null: T::Native,
+    raw: fn(T::Native) -> [u8; N],
+    out: &mut Vec<u8>,
+) -> (bool, bool)
+where
+    T: PolarsIntegerType,
+{
+    let mut uses_sentinel = false;
+    for v in values.iter() {
+        uses_sentinel |= v == Some(null);
+        out.extend_from_slice(&raw(v.unwrap_or(null)));
+    }
+    (values.null_count() > 0, uses_sentinel)
+}
+
+/// Header cards and column descriptions to write a parquet file back out as
+/// FITS with: the `fits_header` cards that `--convert` stored, and the unit,
+/// UCD and description on each Arrow field. With `keywords`, every other
+/// keyword (such as `maml`) is added too, as a block of COMMENT cards.
+pub fn parquet_fits_metadata(path: &Path, keywords: bool) -> Result<FitsMetadata> {
+    let mut metadata = FitsMetadata::default();
+    let key_values = ParquetReader::new(File::open(path)?)
+        .get_metadata()?
+        .key_value_metadata()
+        .clone()
+        .unwrap_or_default();
+    for kv in key_values {
+        let value = kv.value.unwrap_or_default();
+        match kv.key.as_str() {
+            "ARROW:schema" => {}
+            "fits_header" => metadata.header.extend(value.lines().map(String::from)),
+            key if keywords => {
+                metadata.header.push(format!("COMMENT {key}:"));
+                for line in value.lines() {
+                    let line: Vec<char> = format!("  {line}").chars().collect();
+                    for chunk in line.chunks(72) {
+                        metadata
+                            .header
+                            .push(format!("COMMENT {}", String::from_iter(chunk)));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    metadata.extname = metadata
+        .header
+        .iter()
+        .find_map(|line| match card_from_line(line)? {
+            Card {
+                value: Some(Value::String(name)),
+                ..
+            } if line.starts_with("EXTNAME ") => Some(name.trim().to_string()),
+            _ => None,
+        });
+
+    let schema = ParquetReader::new(File::open(path)?).schema()?;
+    for (name, field) in schema.iter() {
+        let Some(described) = &field.metadata else {
+            continue;
+        };
+        let described: Vec<(String, String)> = ["unit", "ucd", "description"]
+            .into_iter()
+            .filter_map(|key| Some((key.to_string(), described.get(key)?.to_string())))
+            .collect();
+        if !described.is_empty() {
+            metadata.columns.push((name.to_string(), described));
+        }
+    }
+    Ok(metadata)
+}
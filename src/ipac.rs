@@ -0,0 +1,208 @@
+//! Reading IPAC ASCII tables, the `.tbl` format of IRSA and many older catalogues.
+//!
+//! Lines starting with `\` come first: `\keyword = value` lines are the table's
+//! keywords and `\ text` lines are comments. Then up to four header lines
+//! bounded by `|` give each column's name, type, unit and null value, and the
+//! rows follow, each value lined up between its column's bars.
+//!
+//! | type | dtype |
+//! | --- | --- |
+//! | `int` `long` (`i` `l`) | Int64 |
+//! | `double` `real` `float` (`d` `r` `f`) | Float64 |
+//! | `char` `date` (`c`), or no type line | String |
+//!
+//! Blank values and values equal to the column's null (`null` unless the fourth
+//! header line says otherwise) are read as nulls.
+
+use anyhow::{bail, Context, Result};
+use polars::prelude::*;
+use std::path::Path;
+
+use crate::fits::FitsMetadata;
+
+enum Cells {
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+impl Cells {
+    fn new(ipac_type: &str) -> Result<Self> {
+        Ok(match ipac_type.to_ascii_lowercase().as_str() {
+            "int" | "i" | "long" | "l" => Cells::Int(Vec::new()),
+            "double" | "d" | "real" | "r" | "float" | "f" => Cells::Float(Vec::new()),
+            "char" | "c" | "date" | "" => Cells::Text(Vec::new()),
+            other => bail!("unknown IPAC column type '{other}'"),
+        })
+    }
+
+    fn push(&mut self, text: Option<&str>) -> Result<()> {
+        match self {
+            Cells::Int(v) => v.push(
+                text.map(|t| t.parse::<i64>().with_context(|| format!("'{t}' is not an integer")))
+                    .transpose()?,
+            ),
+            Cells::Float(v) => v.push(
+                text.map(|t| t.parse::<f64>().with_context(|| format!("'{t}' is not a number")))
+                    .transpose()?,
+            ),
+            Cells::Text(v) => v.push(text.map(String::from)),
+        }
+        Ok(())
+    }
+
+    fn into_column(self, name: &str) -> Column {
+        let name = PlSmallStr::from(name);
+        match self {
+            Cells::Int(v) => Column::new(name, v),
+            Cells::Float(v) => Column::new(name, v),
+            Cells::Text(v) => Column::new(name, v),
+        }
+    }
+}
+
+struct IpacColumn {
+    name: String,
+    ipac_type: String,
+    unit: Option<String>,
+    null: String,
+}
+
+struct IpacTable {
+    keywords: Vec<(String, String)>,
+    columns: Vec<IpacColumn>,
+    // Character positions of the bars around each column.
+    bars: Vec<usize>,
+    // Line number and text of each data row.
+    rows: Vec<(usize, String)>,
+}
+
+// The text between bar `index` and the next one, trimmed. IPAC columns are
+// fixed-width in characters, which are only bytes when the line is ASCII.
+fn between_bars<'a>(line: &'a str, bars: &[usize], index: usize) -> &'a str {
+    let byte = |position: usize| {
+        if line.is_ascii() {
+            position.min(line.len())
+        } else {
+            line.char_indices().nth(position).map_or(line.len(), |(at, _)| at)
+        }
+    };
+    let start = byte(bars[index] + 1);
+    let end = byte(bars[index + 1]).max(start);
+    line[start..end].trim()
+}
+
+fn unquoted(value: &str) -> &str {
+    let value = value.trim();
+    for quote in ['\'', '"'] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn parse_ipac(path: &Path, with_rows: bool) -> Result<IpacTable> {
+    let text = std::fs::read_to_string(path)?;
+    let mut keywords = Vec::new();
+    let mut header: Vec<&str> = Vec::new();
+    let mut rows = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if rows.is_empty() && line.starts_with('\\') {
+            let keyword = &line[1..];
+            if keyword.starts_with(' ') {
+                continue;
+            }
+            if let Some((key, value)) = keyword.split_once('=') {
+                keywords.push((key.trim().to_string(), unquoted(value).to_string()));
+            }
+        } else if rows.is_empty() && line.starts_with('|') {
+            header.push(line);
+        } else if !with_rows {
+            break;
+        } else {
+            rows.push((number + 1, line.to_string()));
+        }
+    }
+
+    let Some(names) = header.first() else {
+        bail!("{path:?} has no IPAC column header.");
+    };
+    let bars: Vec<usize> = names
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| *c == '|')
+        .map(|(position, _)| position)
+        .collect();
+    let header_value = |line: usize, index: usize| {
+        header
+            .get(line)
+            .map(|text| between_bars(text, &bars, index).to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let columns = (0..bars.len().saturating_sub(1))
+        .map(|index| IpacColumn {
+            name: between_bars(names, &bars, index).to_string(),
+            ipac_type: header_value(1, index).unwrap_or_default(),
+            unit: header_value(2, index),
+            null: header_value(3, index).unwrap_or_else(|| "null".to_string()),
+        })
+        .collect();
+    Ok(IpacTable {
+        keywords,
+        columns,
+        bars,
+        rows,
+    })
+}
+
+pub fn read_ipac_file(path: &Path) -> Result<LazyFrame> {
+    let table = parse_ipac(path, true)?;
+    let mut cells = table
+        .columns
+        .iter()
+        .map(|column| {
+            Cells::new(&column.ipac_type).with_context(|| format!("column '{}'", column.name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for (number, line) in &table.rows {
+        for (index, column) in table.columns.iter().enumerate() {
+            let value = between_bars(line, &table.bars, index);
+            let value = (!value.is_empty() && value != column.null).then_some(value);
+            cells[index]
+                .push(value)
+                .with_context(|| format!("{path:?} line {number}, column '{}'", column.name))?;
+        }
+    }
+    let columns: Vec<Column> = cells
+        .into_iter()
+        .zip(&table.columns)
+        .map(|(cells, column)| cells.into_column(&column.name))
+        .collect();
+    Ok(DataFrame::new(table.rows.len(), columns)?.lazy())
+}
+
+/// `\keyword = value` lines of an IPAC table, with any quotes around the value removed.
+pub fn read_ipac_keywords(path: &Path) -> Result<Vec<(String, String)>> {
+    Ok(parse_ipac(path, false)?.keywords)
+}
+
+/// Column units of an IPAC table, to carry into another format.
+pub fn read_ipac_metadata(path: &Path) -> Result<FitsMetadata> {
+    let table = parse_ipac(path, false)?;
+    Ok(FitsMetadata {
+        extname: None,
+        header: Vec::new(),
+        columns: table
+            .columns
+            .into_iter()
+            .filter_map(|column| Some((column.name, vec![("unit".to_string(), column.unit?)])))
+            .collect(),
+    })
+}
@@ -1,17 +1,38 @@
 mod cli;
+mod compression;
+mod config;
+mod ecsv;
 mod filter;
+mod fits;
+mod fits_writer;
 mod footer;
+mod ipac;
+mod maml;
 mod printer;
 mod reader;
+mod votable;
 mod write;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::compression::{decompress, without_compression_extension};
+use crate::config::parquet_defaults;
+use crate::ecsv::read_ecsv_metadata;
 use crate::filter::parse_selection_string;
+use crate::fits::{image_summary, read_fits_metadata, FitsMetadata};
+use crate::fits_writer::{parquet_fits_metadata, write_fits};
 use crate::footer::{delete_keyword_metadata, write_keyword_metadata};
+use crate::ipac::read_ipac_metadata;
+use crate::maml::maml_from_fits;
 use crate::printer::*;
-use crate::reader::{read_file, which_file, FileType};
-use crate::write::write_parquet;
+use crate::reader::{
+    file_type, format_from_extension, read_file, read_stdin, CsvOptions, FileType, ReadOptions,
+};
+use crate::votable::{read_votable_metadata, write_votable};
+use crate::write::{
+    carried_keywords, write_converted_fits, write_csv, write_ecsv, write_ipc, write_json, write_parquet,
+    ParquetOptions, PendingOutput,
+};
 use anyhow::{bail, Result};
 use clap::ArgMatches;
 use polars::prelude::*;
@@ -28,11 +49,62 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
         );
     }
 
+    let read_options = ReadOptions {
+        hdu: matches.get_one::<String>("hdu").cloned(),
+        explode_vectors: matches.get_flag("explode-vectors"),
+        tail_rows: None,
+        format: matches
+            .get_one::<String>("format")
+            .map(|name| FileType::from_name(name))
+            .transpose()?,
+        csv: csv_options(&matches)?,
+        stdin: None,
+    };
+    let to = matches
+        .get_one::<String>("to")
+        .map(|name| FileType::from_name(name))
+        .transpose()?;
+    // The config file is only read when something might be written.
+    let parquet_options = if matches.contains_id("outfile") || matches.get_flag("convert") {
+        parquet_options(&matches).or(parquet_defaults()?)
+    } else {
+        ParquetOptions::default()
+    };
+
+    if files.clone().filter(|file| *file == "-").count() > 1 {
+        bail!("Standard input can only be read once; '-' was given more than once.");
+    }
+
     for file in files {
-        let file_path = PathBuf::from(file);
-        if !file_path.exists() {
+        // `-` is standard input, held in memory or spooled to a temporary file.
+        let stdin = match file.as_str() {
+            "-" => Some(read_stdin(&read_options)?),
+            _ => None,
+        };
+        let file_path = match &stdin {
+            Some(stdin) => stdin.path.clone(),
+            None => PathBuf::from(file),
+        };
+        if stdin.is_none() && !file_path.exists() {
             bail!("No file follows glob pattern '{}'", file_path.display());
         }
+        if stdin.is_some()
+            && (matches.contains_id("insert-metadata") || matches.contains_id("delete-kw-metadata"))
+        {
+            bail!("Keyword metadata can't be edited in standard input.");
+        }
+        if file_path.is_dir()
+            && (["insert-metadata", "delete-kw-metadata", "keyword"]
+                .iter()
+                .any(|id| matches.contains_id(id))
+                || matches.get_flag("list-kw-metadata"))
+        {
+            bail!("'{}' is a dataset directory; keyword metadata is read from its files one at a time.", file_path.display());
+        }
+        let read_options = ReadOptions {
+            stdin: stdin.as_ref().and_then(|stdin| stdin.bytes.clone()),
+            ..read_options.clone()
+        };
 
         if let Some(meta_args) = matches.get_many::<String>("insert-metadata") {
             let mut arguments = meta_args.map(|f| f.to_string());
@@ -71,7 +143,62 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
             continue;
         }
 
-        let mut lazy_frame = read_file(file_path.clone())?;
+        // Compressed inputs are read from a decompressed copy, but whatever is
+        // written is still named after the original.
+        let source = PathBuf::from(file);
+        let decompressed = match read_options.stdin {
+            Some(_) => None,
+            None => decompress(&file_path)?,
+        };
+        let file_path = match &decompressed {
+            Some(copy) => copy.path.clone(),
+            None => file_path,
+        };
+
+        let input_type = file_type(&file_path, &read_options)?;
+        let is_fits = input_type == FileType::Fits;
+
+        if matches.get_flag("list-hdus") {
+            if !is_fits {
+                bail!("--list-hdus only applies to FITS files.");
+            }
+            print_hdu_list(&file_path)?;
+            continue;
+        }
+
+        if let Some(keyword) = matches.get_one::<String>("keyword") {
+            if is_fits {
+                print_fits_keyword(&file_path, read_options.hdu.as_deref(), keyword)?;
+            } else if matches!(input_type, FileType::Ecsv | FileType::Ipac) {
+                print_text_keyword(&file_path, input_type, keyword)?;
+            } else {
+                print_keyword_metadata(&file_path, keyword)?;
+            }
+            continue;
+        }
+
+        if matches.get_flag("list-kw-metadata") {
+            if is_fits {
+                list_fits_keywords(&file_path, read_options.hdu.as_deref())?;
+            } else if matches!(input_type, FileType::Ecsv | FileType::Ipac) {
+                list_text_keywords(&file_path, input_type)?;
+            } else {
+                list_keyword_metadata(&file_path)?;
+            }
+            continue;
+        }
+
+        let whole_file = !matches.contains_id("columns") && !matches.contains_id("filter");
+        if is_fits && whole_file && (matches.get_flag("summary") || matches.get_flag("stats")) {
+            // Images are summarised straight from the pixels, not as a pixel table.
+            if let Some(summary) = image_summary(&file_path, read_options.hdu.as_deref())? {
+                print_image_summary(&summary);
+                continue;
+            }
+        }
+
+        let mut lazy_frame = read_file(file_path.clone(), &read_options)?;
+        let json = matches.get_flag("json");
         let mut columns_selected = false;
         let mut rows_selected = false;
 
@@ -95,12 +222,18 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
         }
 
         if let Some(outfile_name) = matches.get_one::<String>("outfile") {
-            if rows_selected | columns_selected {
-                write_parquet(&lazy_frame, &PathBuf::from(outfile_name))?;
-                continue;
-            } else {
+            let outfile = PathBuf::from(outfile_name);
+            // Anything `--to` doesn't name, or the extension doesn't say, is parquet.
+            let format = match &to {
+                Some(to) => *to,
+                None => format_from_extension(&outfile).unwrap_or(FileType::Parquet),
+            };
+            if !(rows_selected | columns_selected) && format == input_type {
                 bail!("File not saved. No columns or rows have been selected.")
             }
+            refuse_overwrite(&outfile, &source, matches.get_flag("force"))?;
+            write_output(&mut lazy_frame, &outfile, format, &file_path, &read_options, &parquet_options, &matches)?;
+            continue;
         }
 
         if let Some(header_rows) = matches.get_one::<String>("head") {
@@ -108,7 +241,11 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
                 Ok(no_row) => no_row,
                 Err(_) => bail!("'Number of rows' should be an integer."),
             };
-            print_head(&mut lazy_frame, no_rows)?;
+            if json {
+                print_json(lazy_frame.clone().limit(no_rows))?;
+            } else {
+                print_head(&mut lazy_frame, no_rows)?;
+            }
         }
 
         if let Some(tail_rows) = matches.get_one::<String>("tail") {
@@ -116,35 +253,67 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
                 Ok(no_row) => no_row,
                 Err(_) => bail!("'Number of rows' should be an integer."),
             };
-            print_tail(&lazy_frame, no_rows)?;
+            let tail_frame = if is_fits && !rows_selected {
+                // A FITS scan can start at the tail instead of reading every row.
+                let tail_options = ReadOptions {
+                    tail_rows: Some(no_rows as usize),
+                    ..read_options.clone()
+                };
+                let mut tail_frame = read_file(file_path.clone(), &tail_options)?;
+                if let Some(columns) = matches.get_many::<String>("columns") {
+                    tail_frame = tail_frame.select(columns.map(col).collect::<Vec<_>>());
+                }
+                tail_frame
+            } else {
+                lazy_frame.clone()
+            };
+            if json {
+                print_json(tail_frame.tail(no_rows))?;
+            } else {
+                print_tail(&tail_frame, no_rows)?;
+            }
         }
 
-        if let Some(keyword) = matches.get_one::<String>("keyword") {
-            print_keyword_metadata(&file_path, keyword)?;
+        // The rows -H and -t print are all that's wanted from the file.
+        if matches.contains_id("head") || matches.contains_id("tail") {
             continue;
         }
 
         if matches.get_flag("names") {
             print_column_names(&mut lazy_frame)?;
         } else if matches.get_flag("data") {
-            print_only_data(lazy_frame, false)?;
+            if json {
+                print_json(lazy_frame)?;
+            } else {
+                print_only_data(lazy_frame, false)?;
+            }
         } else if matches.get_flag("stats") {
-            print_stats(lazy_frame)?;
+            print_stats(lazy_frame, input_type.engine())?;
         } else if matches.get_flag("schema") {
             print_schema(lazy_frame)?;
         } else if matches.get_flag("summary") {
             print_summary(lazy_frame)?;
         } else if matches.get_flag("peak") {
             peak(lazy_frame)?;
-        } else if matches.get_flag("list-kw-metadata") {
-            list_keyword_metadata(&file_path)?;
         } else if matches.get_flag("convert") {
-            let outfile = match which_file(&file_path)? {
-                FileType::Csv => PathBuf::from(file.replace(".csv", "_converted.parquet")),
-                FileType::Fits => PathBuf::from(file.replace(".fits", "_converted.parquet")),
-                FileType::Parquet => panic!("File is already a parquet!"),
+            let to = to.unwrap_or(FileType::Parquet);
+            let extension = match (input_type, to) {
+                (FileType::Fits, FileType::Fits) => bail!("File is already a FITS file!"),
+                (FileType::Parquet, FileType::Parquet) => bail!("File is already a parquet!"),
+                (FileType::Csv, FileType::Csv) => bail!("File is already a csv file!"),
+                (FileType::Ipc, FileType::Ipc) => bail!("File is already an Arrow IPC file!"),
+                (FileType::Json, FileType::Json) | (FileType::NdJson, FileType::NdJson) => {
+                    bail!("File is already JSON!")
+                }
+                (FileType::VoTable, FileType::VoTable) => bail!("File is already a VOTable!"),
+                (FileType::Ecsv, FileType::Ecsv) => bail!("File is already an ECSV file!"),
+                (_, to) => to.extension(),
             };
-            write_parquet(&lazy_frame, &outfile).unwrap();
+            let outfile = converted_name(&without_compression_extension(&source), extension);
+            refuse_overwrite(&outfile, &source, matches.get_flag("force"))?;
+            write_output(&mut lazy_frame, &outfile, to, &file_path, &read_options, &parquet_options, &matches)?;
+        } else if json {
+            print_json(lazy_frame)?;
         } else {
             print_only_data(lazy_frame, true)?;
         }
@@ -153,6 +322,173 @@ fn handle_arguments(matches: ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// The csv parsing options given on the command line.
+fn csv_options(matches: &ArgMatches) -> Result<CsvOptions> {
+    let mut options = CsvOptions {
+        no_header: matches.get_flag("no-header"),
+        comment_prefix: matches.get_one::<String>("comment-prefix").cloned(),
+        skip_rows: matches.get_one::<usize>("skip-rows").copied().unwrap_or(0),
+        infer_schema_length: matches.get_one::<usize>("infer-schema-length").copied(),
+        ..CsvOptions::default()
+    };
+    if let Some(separator) = matches.get_one::<String>("separator") {
+        options.set_separator(separator)?;
+    }
+    if let Some(nulls) = matches.get_many::<String>("null-values") {
+        options.null_values = nulls.cloned().collect();
+    }
+    for entry in matches.get_many::<String>("schema-overrides").unwrap_or_default() {
+        options.add_dtype(entry)?;
+    }
+    if let Some(quote) = matches.get_one::<String>("quote-char") {
+        options.quote_char = match quote.as_str() {
+            "none" => Some(None),
+            quote if quote.len() == 1 => Some(Some(quote.as_bytes()[0])),
+            quote => bail!("'{quote}' is not a quote character; give a single character or 'none'."),
+        };
+    }
+    Ok(options)
+}
+
+fn parquet_options(matches: &ArgMatches) -> ParquetOptions {
+    let switch = |id: &str| matches.get_one::<String>(id).map(|value| value == "on");
+    ParquetOptions {
+        compression: matches.get_one::<String>("compression").cloned(),
+        compression_level: matches.get_one::<i64>("compression-level").copied(),
+        row_group_size: matches.get_one::<usize>("row-group-size").copied(),
+        data_page_size: matches.get_one::<usize>("data-page-size").copied(),
+        statistics: switch("statistics"),
+        dictionary: switch("dictionary"),
+    }
+}
+
+/// `name.csv` becomes `name_converted.<extension>`, whatever the input's extension,
+/// and standard input becomes `stdin_converted.<extension>`.
+fn converted_name(file_path: &Path, extension: &str) -> PathBuf {
+    let stem = match file_path.file_stem().unwrap_or_default().to_string_lossy() {
+        stem if stem == "-" => "stdin".into(),
+        stem => stem,
+    };
+    file_path.with_file_name(format!("{stem}_converted.{extension}"))
+}
+
+/// Outputs never replace an existing file, least of all the input, without `--force`.
+fn refuse_overwrite(outfile: &Path, source: &Path, force: bool) -> Result<()> {
+    if force || !outfile.exists() {
+        return Ok(());
+    }
+    let same_file = match (outfile.canonicalize(), source.canonicalize()) {
+        (Ok(outfile), Ok(source)) => outfile == source,
+        _ => false,
+    };
+    if same_file {
+        bail!(
+            "'{}' is the input file; pass -F/--force to replace it with the output.",
+            outfile.display()
+        );
+    }
+    bail!(
+        "'{}' already exists; pass -F/--force to overwrite it.",
+        outfile.display()
+    );
+}
+
+/// Writes `lazy_frame`, read from `file_path`, to `outfile` as `format`, with
+/// whatever of the input's metadata that format can hold.
+fn write_output(
+    lazy_frame: &mut LazyFrame,
+    outfile: &Path,
+    format: FileType,
+    file_path: &Path,
+    read_options: &ReadOptions,
+    parquet_options: &ParquetOptions,
+    matches: &ArgMatches,
+) -> Result<()> {
+    let input_type = file_type(file_path, read_options)?;
+    // File sinks only run on the streaming engine, so a scan it can't run is
+    // collected first and the table streamed from memory.
+    if input_type.engine() != Engine::Streaming {
+        *lazy_frame = lazy_frame.clone().collect_with_engine(input_type.engine())?.unwrap_single().lazy();
+    }
+    let pending = PendingOutput::new(outfile);
+    let outfile = pending.temp_path();
+    match format {
+        FileType::Fits => {
+            let metadata = fits_output_metadata(file_path, read_options, matches)?;
+            write_fits(lazy_frame, outfile, &metadata)?;
+        }
+        FileType::Csv => write_csv(lazy_frame, outfile)?,
+        FileType::Ipc => write_ipc(lazy_frame, outfile)?,
+        FileType::Json => write_json(lazy_frame, outfile, JsonFormat::Json)?,
+        FileType::NdJson => write_json(lazy_frame, outfile, JsonFormat::JsonLines)?,
+        FileType::VoTable => {
+            let metadata = fits_output_metadata(file_path, read_options, matches)?;
+            write_votable(lazy_frame, outfile, &metadata)?;
+        }
+        FileType::Ecsv => {
+            let metadata = fits_output_metadata(file_path, read_options, matches)?;
+            write_ecsv(lazy_frame, outfile, &metadata)?;
+        }
+        FileType::Ipac => bail!("IPAC tables can be read but not written."),
+        FileType::Parquet
+            if matches!(input_type, FileType::Fits | FileType::VoTable | FileType::Ecsv | FileType::Ipac) =>
+        {
+            let metadata = fits_output_metadata(file_path, read_options, matches)?;
+            let maml = if matches.get_flag("maml") {
+                let dataset = file_path.file_stem().unwrap_or_default().to_string_lossy();
+                let schema = lazy_frame.collect_schema()?;
+                Some(maml_from_fits(&dataset, &metadata, &schema))
+            } else {
+                None
+            };
+            write_converted_fits(lazy_frame, outfile, &metadata, maml, parquet_options)?;
+        }
+        FileType::Parquet => {
+            let (metadata, keywords) = carried_parquet_metadata(file_path, input_type, lazy_frame, matches)?;
+            write_parquet(lazy_frame, outfile, &metadata, keywords, parquet_options)?;
+        }
+    }
+    pending.commit()
+}
+
+/// What a parquet written with `-o` keeps from a parquet input: its keyword
+/// metadata, with `maml` fields trimmed to the columns kept, and each column's
+/// unit, UCD and description. Nothing is kept with `--drop-keywords`.
+fn carried_parquet_metadata(
+    file_path: &Path,
+    input_type: FileType,
+    lazy_frame: &mut LazyFrame,
+    matches: &ArgMatches,
+) -> Result<(FitsMetadata, Vec<(String, String)>)> {
+    if input_type != FileType::Parquet || file_path.is_dir() || matches.get_flag("drop-keywords") {
+        return Ok((FitsMetadata::default(), Vec::new()));
+    }
+    let schema = lazy_frame.collect_schema()?;
+    let columns: Vec<&str> = schema.iter_names().map(|name| name.as_str()).collect();
+    let metadata = parquet_fits_metadata(file_path, false)?;
+    Ok((metadata, carried_keywords(file_path, &columns)?))
+}
+
+/// Header cards and column descriptions to carry into a FITS, VOTable or ECSV output.
+fn fits_output_metadata(
+    file_path: &Path,
+    read_options: &ReadOptions,
+    matches: &ArgMatches,
+) -> Result<FitsMetadata> {
+    Ok(match file_type(file_path, read_options)? {
+        FileType::Fits => read_fits_metadata(file_path, read_options.hdu.as_deref())?,
+        // A partitioned dataset has no single footer to take keywords from.
+        FileType::Parquet if file_path.is_dir() => FitsMetadata::default(),
+        FileType::Parquet => parquet_fits_metadata(file_path, matches.get_flag("header-keywords"))?,
+        FileType::VoTable => read_votable_metadata(file_path)?,
+        FileType::Ecsv => read_ecsv_metadata(file_path)?,
+        FileType::Ipac => read_ipac_metadata(file_path)?,
+        FileType::Csv | FileType::Ipc | FileType::Json | FileType::NdJson => {
+            FitsMetadata::default()
+        }
+    })
+}
+
 fn main() -> Result<()> {
     let matches = cli::build_cli().get_matches();
     handle_arguments(matches)?;
@@ -0,0 +1,137 @@
+//! Generating MAML (https://github.com/asgr/MAML) blocks from a file's own metadata.
+
+use polars::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
+use yaml_rust2::{Yaml, YamlEmitter, YamlLoader};
+
+use crate::fits::FitsMetadata;
+
+// MAML names for the polars dtypes; anything nested keeps the polars name.
+fn maml_data_type(dtype: &DataType) -> String {
+    match dtype {
+        DataType::Boolean => "bool".to_string(),
+        DataType::String => "string".to_string(),
+        dtype if dtype.is_primitive_numeric() => format!("{dtype:?}").to_lowercase(),
+        dtype => dtype.to_string(),
+    }
+}
+
+// `text` as a YAML scalar, quoted only if it would otherwise be read as
+// something else (a mapping, a comment, an alias, a number...).
+fn scalar(text: &str) -> String {
+    let mut out = String::new();
+    YamlEmitter::new(&mut out)
+        .dump(&Yaml::String(text.to_string()))
+        .expect("a string always emits");
+    out.trim_start_matches("---").trim_start_matches('\n').to_string()
+}
+
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Today's date as YYYY-MM-DD, using the days-to-civil conversion from
+// http://howardhinnant.github.io/date_algorithms.html.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// A starting MAML block for a table converted from FITS, VOTable, ECSV or
+/// IPAC: the table name, today's date and one `fields` entry per column with
+/// the unit, UCD and description its header gives.
+pub fn maml_from_fits(dataset: &str, metadata: &FitsMetadata, schema: &Schema) -> String {
+    let mut maml = vec![
+        "# Autogenerated maml from the table's header. Check and fill in the survey details before publishing. This comment can be deleted.".to_string(),
+        format!("dataset: {}", scalar(dataset)),
+        format!("table: {}", scalar(metadata.extname.as_deref().unwrap_or(dataset))),
+        "version: 1".to_string(),
+        format!("date: '{}'", today()),
+        "MAML_version: 1.1".to_string(),
+        "fields:".to_string(),
+    ];
+    for (name, dtype) in schema.iter() {
+        maml.push(format!("- name: {}", scalar(name)));
+        for (key, value) in metadata.column(name).unwrap_or_default() {
+            match key.as_str() {
+                "unit" => maml.push(format!("  unit: {}", scalar(value))),
+                "ucd" => maml.push(format!("  ucd: {}", scalar(value))),
+                "description" => maml.push(format!("  info: {}", quoted(value))),
+                _ => {}
+            }
+        }
+        maml.push(format!("  data_type: {}", maml_data_type(dtype)));
+    }
+    maml.join("\n") + "\n"
+}
+
+// The `name` of a `fields` entry, if this line of it gives one, unquoted the
+// way YAML reads it.
+fn field_name(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let line = line.strip_prefix('-').unwrap_or(line).trim_start();
+    let name = line.strip_prefix("name:")?.trim();
+    let parsed = YamlLoader::load_from_str(name).ok();
+    Some(match parsed.as_ref().and_then(|docs| docs.first()?.as_str()) {
+        Some(unquoted) => unquoted.to_string(),
+        None => name.to_string(),
+    })
+}
+
+// Moves a finished `fields` entry into `kept` if it describes one of `columns`.
+fn flush_field<'a>(entry: &mut Vec<&'a str>, kept: &mut Vec<&'a str>, columns: &[&str]) {
+    // Entries without a name can't be matched to a column, so they stay.
+    let name = entry.iter().find_map(|line| field_name(line));
+    if name.is_none_or(|name| columns.contains(&name.as_str())) {
+        kept.append(entry);
+    }
+    entry.clear();
+}
+
+/// `maml` with its `fields` list cut down to the entries for `columns`. Every
+/// other line, comments included, is kept as it was written.
+pub fn trim_maml_fields(maml: &str, columns: &[&str]) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut in_fields = false;
+    let mut entry: Vec<&str> = Vec::new();
+    let mut entry_indent = None;
+
+    for line in maml.lines() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if !in_fields {
+            kept.push(line);
+            in_fields = line.trim_end() == "fields:";
+        } else if trimmed.is_empty() || trimmed.starts_with('#') {
+            if entry.is_empty() {
+                kept.push(line);
+            } else {
+                entry.push(line);
+            }
+        } else if indent == 0 && !trimmed.starts_with('-') {
+            flush_field(&mut entry, &mut kept, columns);
+            kept.push(line);
+            in_fields = line.trim_end() == "fields:";
+            entry_indent = None;
+        } else if trimmed.starts_with('-') && entry_indent.is_none_or(|at| at == indent) {
+            flush_field(&mut entry, &mut kept, columns);
+            entry.push(line);
+            entry_indent = Some(indent);
+        } else {
+            entry.push(line);
+        }
+    }
+    flush_field(&mut entry, &mut kept, columns);
+    kept.join("\n") + "\n"
+}
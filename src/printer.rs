@@ -1,14 +1,65 @@
 // printing module handling all printing functions and routines
-use anyhow::{Ok, Result};
+use anyhow::{bail, Ok, Result};
 use colored::Colorize;
 use polars::prelude::*;
 use polars::prelude::{Column, CsvWriter};
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::ecsv::read_ecsv_keywords;
+use crate::ipac::read_ipac_keywords;
+use crate::reader::FileType;
+use crate::fits::{fits_headers, list_fits_hdus, HduSummary, ImageSummary};
+
+// Nested values as a single field without spaces, e.g. `[1.5,2.5]` or `{1.0,-2.0}`,
+// so that space-separated output keeps one field per column.
+fn compact_value(value: AnyValue) -> String {
+    let join = |values: Vec<String>| values.join(",");
+    match value {
+        AnyValue::List(s) | AnyValue::Array(s, _) => {
+            format!("[{}]", join(s.iter().map(compact_value).collect()))
+        }
+        AnyValue::Struct(..) => {
+            format!(
+                "{{{}}}",
+                join(value._iter_struct_av().map(compact_value).collect())
+            )
+        }
+        AnyValue::StructOwned(payload) => {
+            format!(
+                "{{{}}}",
+                join(payload.0.into_iter().map(compact_value).collect())
+            )
+        }
+        AnyValue::String(s) => s.to_string(),
+        AnyValue::StringOwned(s) => s.to_string(),
+        other => other.to_string(),
+    }
+}
+
+// CSV has no nested types, so List/Array/Struct columns are written as text.
+pub(crate) fn nested_as_text(df: DataFrame) -> Result<DataFrame> {
+    let columns = df
+        .columns()
+        .iter()
+        .map(|column| {
+            if !column.dtype().is_nested() {
+                return column.clone();
+            }
+            let text: StringChunked = column
+                .as_materialized_series()
+                .iter()
+                .map(|value| (!value.is_null()).then(|| compact_value(value)))
+                .collect();
+            text.with_name(column.name().clone()).into_column()
+        })
+        .collect();
+    Ok(DataFrame::new(df.height(), columns)?)
+}
 
 pub fn print_only_data(lazy_frame: LazyFrame, include_header: bool) -> Result<()> {
     let mut out = std::io::stdout().lock();
-    let mut df = lazy_frame.collect()?;
+    let mut df = nested_as_text(lazy_frame.collect()?)?;
 
     CsvWriter::new(&mut out)
         .include_header(include_header)
@@ -17,6 +68,17 @@ pub fn print_only_data(lazy_frame: LazyFrame, include_header: bool) -> Result<()
     Ok(())
 }
 
+/// Prints each row as a JSON object on its own line.
+pub fn print_json(lazy_frame: LazyFrame) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    let mut df = lazy_frame.collect()?;
+
+    JsonWriter::new(&mut out)
+        .with_json_format(JsonFormat::JsonLines)
+        .finish(&mut df)?;
+    Ok(())
+}
+
 pub fn print_schema(lazy_frame: LazyFrame) -> Result<()> {
     let mut mut_lazyframe = lazy_frame;
     let schema = mut_lazyframe.collect_schema()?;
@@ -72,6 +134,117 @@ pub fn print_keyword_metadata(file_name: &PathBuf, keyword: &str) -> Result<()>
     Ok(())
 }
 
+fn hdu_name(hdu: &HduSummary) -> &str {
+    match &hdu.extname {
+        Some(name) => name,
+        None if hdu.index == 0 => "PRIMARY",
+        None => "-",
+    }
+}
+
+pub fn print_fits_keyword(file_name: &Path, hdu: Option<&str>, keyword: &str) -> Result<()> {
+    let headers = fits_headers(file_name, hdu)?;
+    let keyword = keyword.to_uppercase();
+    let mut cards = headers
+        .iter()
+        .flat_map(|(_, cards)| cards)
+        .filter(|card| card.keyword == keyword)
+        .collect::<Vec<_>>();
+    if cards.is_empty() {
+        bail!(
+            "'{keyword}' is not in the FITS header. Run `dog --list-keywords {}` to see the keywords.",
+            file_name.display()
+        );
+    }
+    // Every HISTORY or COMMENT line is printed, but for ordinary keywords the
+    // extension's card takes precedence over the primary header's.
+    if keyword != "HISTORY" && keyword != "COMMENT" {
+        cards.drain(..cards.len() - 1);
+    }
+
+    for card in cards {
+        match (&card.value, &card.comment) {
+            (Some(value), Some(comment)) => {
+                println!("{value} {}", format!("/ {comment}").dimmed())
+            }
+            (Some(value), None) => println!("{value}"),
+            (None, Some(comment)) => println!("{comment}"),
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn list_fits_keywords(file_name: &Path, hdu: Option<&str>) -> Result<()> {
+    for (summary, cards) in fits_headers(file_name, hdu)? {
+        println!(
+            "{}",
+            format!("HDU {} {}", summary.index, hdu_name(&summary)).bold()
+        );
+        for card in cards {
+            let mut line = format!("{:<8}", card.keyword).bold().magenta().to_string();
+            if let Some(value) = &card.value {
+                line.push_str(&format!(" = {value}"));
+            }
+            if let Some(comment) = &card.comment {
+                let separator = if card.value.is_some() { " / " } else { " " };
+                line.push_str(&format!("{}", format!("{separator}{comment}").dimmed()));
+            }
+            println!("{line}");
+        }
+    }
+    Ok(())
+}
+
+// `meta` of an ECSV file or `\keyword` lines of an IPAC table.
+fn text_keywords(file_name: &Path, file_type: FileType) -> Result<(&'static str, Vec<(String, String)>)> {
+    match file_type {
+        FileType::Ecsv => Ok(("ECSV meta", read_ecsv_keywords(file_name)?)),
+        FileType::Ipac => Ok(("IPAC header", read_ipac_keywords(file_name)?)),
+        _ => bail!("{file_name:?} has no keywords in its text header."),
+    }
+}
+
+pub fn print_text_keyword(file_name: &Path, file_type: FileType, keyword: &str) -> Result<()> {
+    let (place, keywords) = text_keywords(file_name, file_type)?;
+    match keywords.iter().find(|(key, _)| key == keyword) {
+        Some((_, value)) => println!("{}", value.trim_end()),
+        None => bail!(
+            "'{keyword}' is not in the {place}. Run `dog --list-keywords {}` to see the keywords.",
+            file_name.display()
+        ),
+    }
+    Ok(())
+}
+
+pub fn list_text_keywords(file_name: &Path, file_type: FileType) -> Result<()> {
+    for (key, _) in text_keywords(file_name, file_type)?.1 {
+        println!("{}", key.bold().magenta());
+    }
+    Ok(())
+}
+
+pub fn print_hdu_list(file_name: &Path) -> Result<()> {
+    let hdus = list_fits_hdus(file_name)?;
+    let name_width = hdus
+        .iter()
+        .filter_map(|hdu| hdu.extname.as_ref().map(|name| name.len()))
+        .max()
+        .unwrap_or(0)
+        .max("PRIMARY".len());
+
+    for hdu in hdus {
+        println!(
+            "{}  {}  {}  {}",
+            format!("{:>3}", hdu.index).bold(),
+            format!("{:<name_width$}", hdu_name(&hdu)).magenta(),
+            format!("{:<16}", hdu.kind).green(),
+            hdu.dimensions
+        );
+    }
+    Ok(())
+}
+
 pub fn print_column_names(lazy_frame: &mut LazyFrame) -> Result<()> {
     let col_names: Vec<String> = lazy_frame
         .collect_schema()?
@@ -91,7 +264,14 @@ fn print_catlike(lazy_frame: LazyFrame) -> Result<()> {
     for i in 0..number_of_rows {
         let row_vals: Vec<String> = columns
             .iter()
-            .map(|s| format!("{}", s.get(i).expect("Shouldn't trigger")))
+            .map(|s| {
+                let value = s.get(i).expect("Shouldn't trigger");
+                if s.dtype().is_nested() {
+                    compact_value(value)
+                } else {
+                    format!("{}", value)
+                }
+            })
             .collect();
         println!("{}", row_vals.join(" "));
     }
@@ -182,6 +362,48 @@ pub fn print_summary(lazy_frame: LazyFrame) -> Result<()> {
     Ok(())
 }
 
+pub fn print_image_summary(summary: &ImageSummary) {
+    let stat = |value: Option<f64>| value.map_or("null".to_string(), |v| v.to_string());
+    println!(
+        "{}",
+        format!(
+            "HDU {} {} ({})",
+            summary.hdu.index,
+            hdu_name(&summary.hdu),
+            summary.hdu.kind
+        )
+        .bold()
+    );
+    println!("{}{}", "Shape: ".bold(), summary.hdu.dimensions.green());
+    println!(
+        "{}{}",
+        "BITPIX: ".bold(),
+        summary.bitpix.to_string().green()
+    );
+    println!(
+        "{}{}",
+        "BSCALE: ".bold(),
+        summary.bscale.to_string().green()
+    );
+    println!("{}{}", "BZERO: ".bold(), summary.bzero.to_string().green());
+    if let Some(unit) = &summary.unit {
+        println!("{}{}", "BUNIT: ".bold(), unit.green());
+    }
+    println!(
+        "{}{}\n",
+        "Pixels: ".bold(),
+        summary.pixels.to_string().green()
+    );
+
+    println!("{}:", "value".bold());
+    println!("---------------");
+    println!("min: {}", stat(summary.min).green());
+    println!("mean: {}", stat(summary.mean).green());
+    println!("max: {}", stat(summary.max).green());
+    println!("std: {}", stat(summary.std).green());
+    println!("null counts: {}", summary.nulls.to_string().green());
+}
+
 pub fn peak(lazy_frame: LazyFrame) -> Result<()> {
     // prints out the polars data frame as 'peak'.
     println!("{:?}", lazy_frame.collect()?);
@@ -195,11 +417,14 @@ fn fmt_cell(stats: &DataFrame, col: &str) -> Result<String> {
 enum ColKind {
     Numeric,
     Str,
+    Struct,
     Other,
 }
 
 fn classify(dtype: &DataType) -> ColKind {
-    if dtype.is_primitive_numeric() {
+    if dtype.is_struct() {
+        ColKind::Struct
+    } else if dtype.is_primitive_numeric() {
         ColKind::Numeric
     } else if dtype.is_string() {
         ColKind::Str
@@ -207,17 +432,26 @@ fn classify(dtype: &DataType) -> ColKind {
         ColKind::Other // bool, dates, etc.
     }
 }
-pub fn print_stats(lazy_frame: LazyFrame) -> Result<()> {
+pub fn print_stats(lazy_frame: LazyFrame, engine: Engine) -> Result<()> {
     let mut lf = lazy_frame.clone();
     let schema = lf.collect_schema()?;
 
     for (name, dtype) in schema.iter() {
         let n = name.as_str();
-        let c = col(n);
+        // List and Array columns are summarised over all of their elements.
+        let mut c = col(n);
+        let mut leaf = dtype;
+        while let Some(inner) = leaf.inner_dtype() {
+            c = c.explode(ExplodeOptions {
+                empty_as_null: false,
+                keep_nulls: true,
+            });
+            leaf = inner;
+        }
 
         // only this column's expressions
         let mut exprs: Vec<Expr> = vec![c.clone().null_count().alias("nulls")];
-        match classify(dtype) {
+        match classify(leaf) {
             ColKind::Numeric => {
                 exprs.push(c.clone().min().alias("min"));
                 exprs.push(c.clone().mean().alias("mean"));
@@ -230,6 +464,7 @@ pub fn print_stats(lazy_frame: LazyFrame) -> Result<()> {
                 exprs.push(c.clone().max().alias("max"));
                 exprs.push(c.n_unique().alias("nunique"));
             }
+            ColKind::Struct => {}
             ColKind::Other => {
                 exprs.push(c.clone().min().alias("min"));
                 exprs.push(c.max().alias("max"));
@@ -240,12 +475,12 @@ pub fn print_stats(lazy_frame: LazyFrame) -> Result<()> {
         let stats = lazy_frame
             .clone()
             .select(exprs)
-            .collect_with_engine(Engine::Streaming)?
+            .collect_with_engine(engine)?
             .unwrap_single();
 
         println!("{}:", n.bold());
         println!("---------------");
-        match classify(dtype) {
+        match classify(leaf) {
             ColKind::Numeric => {
                 println!("min: {}", fmt_cell(&stats, "min")?.green());
                 println!("mean: {}", fmt_cell(&stats, "mean")?.green());
@@ -258,6 +493,7 @@ pub fn print_stats(lazy_frame: LazyFrame) -> Result<()> {
                 println!("max: {}", fmt_cell(&stats, "max")?.green());
                 println!("unique: {}", fmt_cell(&stats, "nunique")?.green());
             }
+            ColKind::Struct => {}
             ColKind::Other => {
                 println!("min: {}", fmt_cell(&stats, "min")?.green());
                 println!("max: {}", fmt_cell(&stats, "max")?.green());
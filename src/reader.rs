@@ -1,211 +1,504 @@
-use fitsio_pure_tsl::compat::fitsfile::FitsFile;
 use polars::prelude::*;
-use polars::{frame::DataFrame};
+use std::fs::File;
+use std::io::{Cursor, Read};
 use std::path::{PathBuf, Path};
-use rayon::prelude::*;
-use anyhow::{Result, Context, anyhow};
+use anyhow::{Result, Context, anyhow, bail};
+use tempfile::TempDir;
 
+use crate::compression::{without_compression_extension, Compression};
+use crate::ecsv::{read_ecsv_header, EcsvHeader, ECSV_SIGNATURE};
+use crate::fits::read_fits_file;
+use crate::ipac::read_ipac_file;
+use crate::votable::read_votable_file;
 
+
+/// Options that change how an input file is read into a `LazyFrame`.
+#[derive(Default, Clone)]
+pub struct ReadOptions {
+    /// FITS HDU to read, either an index or an EXTNAME. Defaults to the first table.
+    pub hdu: Option<String>,
+    /// Split FITS vector columns into one scalar column per element.
+    pub explode_vectors: bool,
+    /// Only the last rows will be looked at, so a FITS scan can skip the rest.
+    pub tail_rows: Option<usize>,
+    /// Format given with `--format`, trusted over whatever the file looks like.
+    pub format: Option<FileType>,
+    /// How csv text is split into rows and columns.
+    pub csv: CsvOptions,
+    /// Standard input, for the formats that are read from memory rather than a file.
+    pub stdin: Option<Arc<Vec<u8>>>,
+}
+
+/// Csv parsing options. Left at their defaults, a file is read the way polars
+/// reads csv, except that a `.tsv` file is split on tabs.
+#[derive(Default, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub separator: Option<u8>,
+    /// Split on any run of spaces and tabs, as in column-aligned text tables.
+    pub whitespace: bool,
+    pub no_header: bool,
+    pub comment_prefix: Option<String>,
+    /// Values read as nulls in every column.
+    pub null_values: Vec<String>,
+    /// Lines dropped before the header.
+    pub skip_rows: usize,
+    /// Dtypes given for columns instead of inferring them.
+    pub dtypes: Vec<(String, DataType)>,
+    /// Rows used to infer dtypes, with `Some(0)` meaning every row.
+    pub infer_schema_length: Option<usize>,
+    /// Quote character, with `Some(None)` turning quoting off.
+    pub quote_char: Option<Option<u8>>,
+}
+
+impl CsvOptions {
+    /// Parses a `--separator` value: a single character, or `tab`, `space`,
+    /// `whitespace` or `\t`.
+    pub fn set_separator(&mut self, name: &str) -> Result<()> {
+        match name {
+            "whitespace" => self.whitespace = true,
+            "tab" | "\\t" => self.separator = Some(b'\t'),
+            "space" => self.separator = Some(b' '),
+            other if other.len() == 1 => self.separator = Some(other.as_bytes()[0]),
+            other => bail!("'{other}' is not a separator; give a single character, 'tab', 'space' or 'whitespace'."),
+        }
+        Ok(())
+    }
+
+    /// Parses a `--schema-overrides` entry such as `id:str` or `mag:f32`.
+    pub fn add_dtype(&mut self, entry: &str) -> Result<()> {
+        let Some((name, dtype)) = entry.rsplit_once(':') else {
+            bail!("'{entry}' should be COLUMN:TYPE, e.g. 'id:str'.");
+        };
+        let dtype = match dtype.trim().to_ascii_lowercase().as_str() {
+            "str" | "string" | "utf8" => DataType::String,
+            "bool" | "boolean" => DataType::Boolean,
+            "i8" | "int8" => DataType::Int8,
+            "i16" | "int16" => DataType::Int16,
+            "i32" | "int32" => DataType::Int32,
+            "i64" | "int64" | "int" => DataType::Int64,
+            "u8" | "uint8" => DataType::UInt8,
+            "u16" | "uint16" => DataType::UInt16,
+            "u32" | "uint32" => DataType::UInt32,
+            "u64" | "uint64" => DataType::UInt64,
+            "f32" | "float32" => DataType::Float32,
+            "f64" | "float64" | "float" => DataType::Float64,
+            other => bail!("Unknown column type '{other}' in '{entry}'."),
+        };
+        self.dtypes.push((name.trim().to_string(), dtype));
+        Ok(())
+    }
+
+    fn parse_options(&self, parse: CsvParseOptions) -> CsvParseOptions {
+        let mut parse = parse;
+        if let Some(separator) = self.separator {
+            parse = parse.with_separator(separator);
+        }
+        if let Some(prefix) = &self.comment_prefix {
+            parse = parse.with_comment_prefix(Some(prefix.as_str()));
+        }
+        if !self.null_values.is_empty() {
+            let nulls = self.null_values.iter().map(|v| v.as_str().into()).collect();
+            parse = parse.with_null_values(Some(NullValues::AllColumns(nulls)));
+        }
+        if let Some(quote_char) = self.quote_char {
+            parse = parse.with_quote_char(quote_char);
+        }
+        parse
+    }
+
+    fn infer_schema_length(&self) -> Option<usize> {
+        match self.infer_schema_length {
+            Some(0) => None,
+            Some(rows) => Some(rows),
+            None => Some(100),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FileType {
     Fits,
     Csv,
     Parquet,
+    Ipc,
+    /// A JSON array of row objects.
+    Json,
+    /// One JSON row object per line.
+    NdJson,
+    /// An IVOA VOTable, an XML table format.
+    VoTable,
+    /// Astropy's csv with a YAML header of dtypes, units and metadata.
+    Ecsv,
+    /// An IPAC ASCII table, with `|`-bounded header lines.
+    Ipac,
 }
 
-pub fn which_file(file_name: &Path) -> Result<FileType> {
+impl FileType {
+    /// Parses a `--format` value.
+    pub fn from_name(name: &str) -> Result<FileType> {
+        match name.to_ascii_lowercase().as_str() {
+            "parquet" => Ok(FileType::Parquet),
+            "csv" => Ok(FileType::Csv),
+            "fits" => Ok(FileType::Fits),
+            "ipc" | "arrow" | "feather" => Ok(FileType::Ipc),
+            "json" => Ok(FileType::Json),
+            "ndjson" | "jsonl" => Ok(FileType::NdJson),
+            "votable" | "vot" => Ok(FileType::VoTable),
+            "ecsv" => Ok(FileType::Ecsv),
+            "ipac" | "tbl" => Ok(FileType::Ipac),
+            _ => Err(anyhow!("Unknown format '{name}'.")),
+        }
+    }
+
+    /// Extension given to files converted into this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            FileType::Fits => "fits",
+            FileType::Csv => "csv",
+            FileType::Parquet => "parquet",
+            FileType::Ipc => "arrow",
+            FileType::Json => "json",
+            FileType::NdJson => "ndjson",
+            FileType::VoTable => "vot",
+            FileType::Ecsv => "ecsv",
+            FileType::Ipac => "tbl",
+        }
+    }
+
+    /// The engine to query a scan of this format with. FITS tables are read
+    /// through an anonymous scan, which polars' streaming engine can't run, so
+    /// they are queried in memory; projections are still pushed into the scan.
+    pub fn engine(self) -> Engine {
+        match self {
+            FileType::Fits => Engine::InMemory,
+            _ => Engine::Streaming,
+        }
+    }
+}
+
+/// The format an output file is written in, from its extension alone. A
+/// compression extension is looked past, so `cat.csv.gz` is csv.
+pub fn format_from_extension(file_name: &Path) -> Result<FileType> {
+    let file_name = without_compression_extension(file_name);
+    let file_name = file_name.as_path();
     let extension = match file_name.extension() {
         Some(t) => t.to_str().context("Failed to convert OS string to str"),
         None => Err(anyhow!("{file_name:?} has no extension. Don't know how to read it."))
     };
-    match extension? {
-        "parquet" => Ok(FileType::Parquet),
-        "csv" => Ok(FileType::Csv),
-        "fits" => Ok(FileType::Fits),
+    match extension?.to_ascii_lowercase().as_str() {
+        "parquet" | "parq" | "pq" => Ok(FileType::Parquet),
+        "csv" | "tsv" | "txt" => Ok(FileType::Csv),
+        "fits" | "fit" | "fts" => Ok(FileType::Fits),
+        "arrow" | "feather" | "ipc" | "arrows" => Ok(FileType::Ipc),
+        "json" => Ok(FileType::Json),
+        "ndjson" | "jsonl" => Ok(FileType::NdJson),
+        "vot" | "votable" | "xml" => Ok(FileType::VoTable),
+        "ecsv" => Ok(FileType::Ecsv),
+        "tbl" | "ipac" => Ok(FileType::Ipac),
         _ => Err(anyhow!("{file_name:?} has an unsupported extension")),
     }
 }
 
+// The 0xFFFFFFFF continuation marker that opens every message of an Arrow IPC
+// stream; the file format instead starts with `ARROW1`.
+const IPC_STREAM_MARKER: [u8; 4] = [0xff; 4];
+
+// Recognises a file from its first bytes. Parquet, FITS and Arrow IPC all
+// start with a fixed signature. Plain text is JSON if it opens with `[` (an
+// array of rows) or `{` (one row per line), a VOTable if it opens with an XML
+// tag, ECSV if it opens with `# %ECSV`, an IPAC table if it opens with a `\`
+// keyword or `|` header line, and is otherwise taken to be csv.
+fn sniff_format(file_name: &Path) -> Result<Option<FileType>> {
+    let mut start = Vec::with_capacity(512);
+    File::open(file_name)?.take(512).read_to_end(&mut start)?;
+    if let Some(compression) = Compression::from_magic(&start) {
+        bail!("{file_name:?} is {compression:?}-compressed and has to be decompressed before it is read.");
+    }
+    Ok(sniff_bytes(&start))
+}
+
+// `start` as text, less a character cut off at its end by taking only the
+// first bytes of the file.
+fn leading_text(start: &[u8]) -> Option<&str> {
+    match std::str::from_utf8(start) {
+        Ok(text) => Some(text),
+        // An error without a length is an incomplete character at the end.
+        Err(err) if err.error_len().is_none() => std::str::from_utf8(&start[..err.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+fn sniff_bytes(start: &[u8]) -> Option<FileType> {
+    let start = &start[..start.len().min(512)];
+    if start.starts_with(b"PAR1") {
+        Some(FileType::Parquet)
+    } else if start.starts_with(b"SIMPLE  =") {
+        Some(FileType::Fits)
+    } else if start.starts_with(b"ARROW1") || start.starts_with(&IPC_STREAM_MARKER) {
+        Some(FileType::Ipc)
+    } else if let Some(text) = leading_text(start).filter(|text| !text.is_empty() && !text.contains('\0') && text.contains('\n')) {
+        if text.starts_with(ECSV_SIGNATURE) {
+            return Some(FileType::Ecsv);
+        }
+        match text.trim_start().chars().next() {
+            Some('[') => Some(FileType::Json),
+            Some('{') => Some(FileType::NdJson),
+            Some('<') => Some(FileType::VoTable),
+            Some('\\' | '|') => Some(FileType::Ipac),
+            _ => Some(FileType::Csv),
+        }
+    } else {
+        None
+    }
+}
+
+/// The format of an input file: its magic bytes if they are recognised,
+/// otherwise its extension.
+pub fn which_file(file_name: &Path) -> Result<FileType> {
+    if file_name.is_dir() {
+        // A directory is read as a (possibly hive-partitioned) parquet dataset.
+        return Ok(FileType::Parquet);
+    }
+    match sniff_format(file_name)? {
+        // Text files are only trusted to be csv when the extension says nothing else.
+        Some(FileType::Csv) => Ok(format_from_extension(file_name).unwrap_or(FileType::Csv)),
+        Some(file_type) => Ok(file_type),
+        None => format_from_extension(file_name),
+    }
+}
+
+/// `--format` if it was given, otherwise what `which_file` makes of the file
+/// (or of standard input's first bytes).
+pub fn file_type(file_name: &Path, options: &ReadOptions) -> Result<FileType> {
+    match (options.format, &options.stdin) {
+        (Some(format), _) => Ok(format),
+        (None, Some(bytes)) => sniff_bytes(bytes).context("Can't tell the format of standard input; give it with --format."),
+        (None, None) => which_file(file_name),
+    }
+}
+
+/// Standard input, given as the file `-`.
+pub struct Stdin {
+    /// Where the readers that need a file find it: a temporary copy, or `-`
+    /// when the bytes are read from memory.
+    pub path: PathBuf,
+    /// What was read, for the formats that are parsed from memory.
+    pub bytes: Option<Arc<Vec<u8>>>,
+    _spool: Option<TempDir>,
+}
+
+/// Reads standard input. Csv, JSON and Arrow IPC are parsed straight from
+/// memory; everything else (parquet and FITS, which seek, and the formats
+/// whose readers work on a file) is first spooled to a temporary file, as is
+/// compressed input.
+pub fn read_stdin(options: &ReadOptions) -> Result<Stdin> {
+    let mut bytes = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut bytes)?;
+    let compressed = Compression::from_magic(&bytes).is_some();
+    let format = match options.format {
+        Some(format) => Some(format),
+        None => sniff_bytes(&bytes),
+    };
+    match format {
+        Some(FileType::Csv | FileType::Json | FileType::NdJson | FileType::Ipc) if !compressed => {
+            Ok(Stdin {
+                path: PathBuf::from("-"),
+                bytes: Some(Arc::new(bytes)),
+                _spool: None,
+            })
+        }
+        None if !compressed => bail!("Can't tell the format of standard input; give it with --format."),
+        _ => {
+            let dir = tempfile::Builder::new().prefix("dog-").tempdir()?;
+            let name = match format {
+                Some(format) if !compressed => format!("stdin.{}", format.extension()),
+                _ => "stdin".to_string(),
+            };
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes)?;
+            Ok(Stdin {
+                path,
+                bytes: None,
+                _spool: Some(dir),
+            })
+        }
+    }
+}
+
+/// A single parquet file, or a directory of them scanned as one table. In a
+/// directory laid out like `release/field=G09/part-0.parquet`, each `key=value`
+/// level becomes a column, and filters on those columns skip whole partitions.
 pub fn read_parquet_file(file_name: PathBuf)-> Result<LazyFrame> {
-    Ok(LazyFrame::scan_parquet_files(vec![PlRefPath::new(file_name.to_str().expect("Path {file_name:?} is not utf8"))].into(), ScanArgsParquet::default())?)
-}
-
-pub fn read_csv_file(path: PathBuf) -> Result<LazyFrame> {
-    let lf = LazyCsvReader::new(PlRefPath::new(path.to_str().expect("Path {file_name:?} is not utf8"))).finish()?;
-    Ok(lf)
-}
-
-
-pub fn read_fits_file(path: &PathBuf) -> Result<LazyFrame> {
-    let fptr = FitsFile::open(path)?;
-    let hdu = fptr.hdu(1)?;
-    let num_cols: i64 = hdu.read_key(&fptr, "TFIELDS")?;
-
-    // Collect column metadata first
-    let mut col_info = Vec::new();
-    for i in 1..=num_cols {
-        let col_name: String = hdu.read_key(&fptr, &format!("TTYPE{}", i))?;
-        let col_type: String = hdu.read_key(&fptr, &format!("TFORM{}", i))?;
-        col_info.push((i, col_name, col_type));
-    }
-    
-    // Read columns in parallel
-    let results: Vec<Result<Vec<Column>, Box<dyn std::error::Error + Send + Sync>>> = col_info
-        .par_iter()
-        .map(
-            |(_, col_name, col_type)| -> Result<Vec<Column>, Box<dyn std::error::Error + Send + Sync>> {
-                // Each thread needs its own file handle
-                let local_fptr = FitsFile::open(path)?;
-                let local_hdu = local_fptr.hdu(1)?;
-                
-                // Parse the column type to check for vector columns
-                let (repeat_count, type_char) = parse_tform(col_type)?;
-                
-                let columns = if repeat_count > 1 && type_char != 'A' {
-                    // Vector column - read flat data and reshape
-                    match type_char {
-                        'E' => {
-                            let flat_data: Vec<f32> = local_hdu.read_col(&local_fptr, col_name)?;
-                            expand_vector_column_from_flat::<Float32Type>(col_name, flat_data, repeat_count)?
-                        }
-                        'D' => {
-                            let flat_data: Vec<f64> = local_hdu.read_col(&local_fptr, col_name)?;
-                            expand_vector_column_from_flat::<Float64Type>(col_name, flat_data, repeat_count)?
-                        }
-                        'J' => {
-                            let flat_data: Vec<i32> = local_hdu.read_col(&local_fptr, col_name)?;
-                            expand_vector_column_from_flat::<Int32Type>(col_name, flat_data, repeat_count)?
-                        }
-                        'K' => {
-                            let flat_data: Vec<i64> = local_hdu.read_col(&local_fptr, col_name)?;
-                            expand_vector_column_from_flat::<Int64Type>(col_name, flat_data, repeat_count)?
-                        }
-
-                        _ => {
-                            return Err(Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Unsupported vector column type: {}", col_type),
-                            )));
-                        }
-                    }
-                } else {
-                    // Scalar column - single column
-                    let col = match type_char {
-                        'E' => {
-                            let data: Vec<f32> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = Float32Chunked::from_vec(col_name.into(), data);
-                            ca.into_series().into()
-                        }
-                        'D' => {
-                            let data: Vec<f64> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = Float64Chunked::from_vec(col_name.into(), data);
-                            ca.into_series().into()
-                        }
-                        'J' => {
-                            let data: Vec<i32> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = Int32Chunked::from_vec(col_name.into(), data);
-                            ca.into_series().into()
-                        }
-                        'K' => {
-                            let data: Vec<i64> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = Int64Chunked::from_vec(col_name.into(), data);
-                            ca.into_series().into()
-                        }
-                        'A' => {
-                            let data: Vec<String> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = StringChunked::from_iter_values(col_name.into(), data.iter().map(|s| s.as_str()));
-                            ca.into_series().into()
-                        }
-                        'I' => {
-                            let data: Vec<i32> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = Int32Chunked::from_vec(col_name.into(), data);
-                            ca.into_series().into()
-                        }
-                        'L' => {
-                            let data: Vec<bool> = local_hdu.read_col(&local_fptr, col_name)?;
-                            let ca = BooleanChunked::from_iter_values(col_name.into(), data.into_iter());
-                            ca.into_series().into()
-                        }
-                        _ => {
-                            return Err(Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Unsupported column type: {}", col_type),
-                            )));
-                        }
-                    };
-                    vec![col]
-                };
-                
-                Ok(columns)
-            },
-        )
-        .collect();
-    
-    // Flatten the results and collect all columns
-    let mut all_columns = Vec::new();
-    for result in results {
-        let cols = result.map_err(|e| anyhow!("{e}"))?;
-        all_columns.extend(cols);
-    }
-    
-    let df = DataFrame::new(all_columns.first().expect("No columns").len(), all_columns)?;
-    Ok(df.lazy())
-}
-
-// Parse TFORM string like "101E" into (repeat_count=101, type_char='E')
-fn parse_tform(tform: &str) -> Result<(usize, char), Box<dyn std::error::Error + Send + Sync>> {
-    let type_char = tform.chars().last()
-        .ok_or_else(|| std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Empty TFORM string"
-        ))?;
-    
-    let repeat_str: String = tform.chars()
-        .take_while(|c| c.is_numeric())
-        .collect();
-    
-    let repeat_count = if repeat_str.is_empty() {
-        1
+    let args = if file_name.is_dir() {
+        ScanArgsParquet {
+            hive_options: HiveOptions::new_enabled(),
+            ..ScanArgsParquet::default()
+        }
+    } else {
+        ScanArgsParquet::default()
+    };
+    Ok(LazyFrame::scan_parquet_files(vec![PlRefPath::new(file_name.to_str().expect("Path {file_name:?} is not utf8"))].into(), args)?)
+}
+
+/// Arrow IPC in either layout: the file format (Feather v2) is scanned lazily,
+/// while a stream has no footer to scan from and is read in full.
+pub fn read_ipc_file(path: PathBuf) -> Result<LazyFrame> {
+    let mut magic = Vec::with_capacity(6);
+    File::open(&path)?.take(6).read_to_end(&mut magic)?;
+    if magic.starts_with(b"ARROW1") {
+        let path = PlRefPath::new(path.to_str().expect("Path {file_name:?} is not utf8"));
+        Ok(LazyFrame::scan_ipc(path, IpcScanOptions::default(), UnifiedScanArgs::default())?)
+    } else {
+        Ok(IpcStreamReader::new(File::open(&path)?).finish()?.lazy())
+    }
+}
+
+/// A JSON array has to be parsed as a whole; NDJSON is scanned lazily.
+/// Nested objects become struct columns either way.
+pub fn read_json_file(path: PathBuf, file_type: FileType) -> Result<LazyFrame> {
+    if file_type == FileType::NdJson {
+        let path = PlRefPath::new(path.to_str().expect("Path {file_name:?} is not utf8"));
+        return Ok(LazyJsonLineReader::new(path).finish()?);
+    }
+    Ok(JsonReader::new(File::open(&path)?).finish()?.lazy())
+}
+
+// Runs of spaces and tabs outside double quotes become a single tab, and the
+// ends of each line are trimmed, so column-aligned text splits like a tsv.
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        let mut quoted = false;
+        let mut gap = false;
+        for c in line.trim().chars() {
+            if !quoted && (c == ' ' || c == '\t') {
+                gap = true;
+                continue;
+            }
+            if gap {
+                out.push('\t');
+                gap = false;
+            }
+            if c == '"' {
+                quoted = !quoted;
+            }
+            out.push(c);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Csv held in memory, which is how standard input and whitespace-separated
+// text (once its runs of blanks are collapsed) are read.
+fn read_csv_bytes(bytes: Vec<u8>, options: &CsvOptions, dtypes: Option<SchemaRef>) -> Result<LazyFrame> {
+    let (bytes, separator) = if options.whitespace {
+        (collapse_whitespace(&String::from_utf8_lossy(&bytes)).into_bytes(), Some(b'\t'))
+    } else {
+        (bytes, None)
+    };
+    Ok(CsvReadOptions::default()
+        .with_has_header(!options.no_header)
+        .with_skip_rows(options.skip_rows)
+        .with_infer_schema_length(options.infer_schema_length())
+        .with_schema_overwrite(dtypes)
+        .map_parse_options(|parse| match separator {
+            Some(separator) => options.parse_options(parse.with_separator(separator)),
+            None => options.parse_options(parse),
+        })
+        .into_reader_with_file_handle(Cursor::new(bytes))
+        .finish()?
+        .lazy())
+}
+
+// The csv options' dtypes, on top of those an ECSV header declares.
+fn csv_dtypes(ecsv: Option<&EcsvHeader>, options: &CsvOptions) -> Option<SchemaRef> {
+    let mut dtypes = match ecsv {
+        Some(header) => header.schema(),
+        None => Schema::default(),
+    };
+    for (name, dtype) in &options.dtypes {
+        dtypes.with_column(name.as_str().into(), dtype.clone());
+    }
+    (!dtypes.is_empty()).then(|| Arc::new(dtypes))
+}
+
+/// Plain csv has its dtypes inferred. An ECSV file's YAML header is skipped
+/// as comment lines, and the delimiter and dtypes it declares are used instead.
+/// The csv options come last, so they win over either. Whitespace-separated
+/// text has to be rewritten before it is parsed, so it is read eagerly.
+pub fn read_csv_file(path: PathBuf, options: &CsvOptions) -> Result<LazyFrame> {
+    let ecsv = read_ecsv_header(&path)?;
+    let tsv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+
+    let dtypes = csv_dtypes(ecsv.as_ref(), options);
+
+    let lf = if options.whitespace {
+        read_csv_bytes(std::fs::read(&path)?, options, dtypes)?
     } else {
-        repeat_str.parse::<usize>()?
+        let mut reader = LazyCsvReader::new(PlRefPath::new(path.to_str().expect("Path {file_name:?} is not utf8")));
+        if tsv {
+            reader = reader.with_separator(b'\t');
+        }
+        if let Some(header) = &ecsv {
+            reader = reader
+                .with_separator(header.delimiter)
+                .with_comment_prefix(Some("#".into()));
+        }
+        reader
+            .with_has_header(!options.no_header)
+            .with_skip_rows(options.skip_rows)
+            .with_infer_schema_length(options.infer_schema_length())
+            .with_dtype_overwrite(dtypes)
+            .map_parse_options(|parse| options.parse_options(parse))
+            .finish()?
     };
-    
-    Ok((repeat_count, type_char))
-}
-
-// Expand a vector column from flat data (FITS stores vectors as flattened arrays)
-fn expand_vector_column_from_flat<T: PolarsNumericType>(
-    base_name: &str,
-    flat_data: Vec<T::Native>,
-    n_elements: usize,
-) -> Result<Vec<Column>, Box<dyn std::error::Error + Send + Sync>>
-where
-    T::Native: Send + Sync + Clone,
-    ChunkedArray<T>: IntoSeries,
-{
-    let n_rows = flat_data.len() / n_elements;
-    let mut columns = Vec::new();
-    
-    for i in 0..n_elements {
-        // Extract every n_elements-th value starting at offset i
-        let col_data: Vec<T::Native> = (0..n_rows)
-            .map(|row| flat_data[row * n_elements + i])
-            .collect();
-        
-        let col_name = format!("{}_{}", base_name, i);
-        let ca = ChunkedArray::<T>::from_vec(col_name.into(), col_data);
-        columns.push(ca.into_series().into());
-    }
-    
-    Ok(columns)
-}
-
-
-
-pub fn read_file(file_name: PathBuf) -> Result<LazyFrame> {
-    match which_file(&file_name)? {
-        FileType::Csv => Ok(read_csv_file(file_name)?),
+    match ecsv {
+        Some(header) if !header.vector_columns().is_empty() => Ok(lf.with_columns(header.vector_columns())),
+        _ => Ok(lf),
+    }
+}
+
+// Standard input that `read_stdin` kept in memory.
+fn read_stdin_bytes(bytes: &[u8], file_type: FileType, options: &ReadOptions) -> Result<LazyFrame> {
+    let cursor = Cursor::new(bytes.to_vec());
+    Ok(match file_type {
+        FileType::Csv => read_csv_bytes(bytes.to_vec(), &options.csv, csv_dtypes(None, &options.csv))?,
+        FileType::Json => JsonReader::new(cursor).finish()?.lazy(),
+        FileType::NdJson => JsonReader::new(cursor)
+            .with_json_format(JsonFormat::JsonLines)
+            .finish()?
+            .lazy(),
+        FileType::Ipc if bytes.starts_with(b"ARROW1") => IpcReader::new(cursor).finish()?.lazy(),
+        FileType::Ipc => IpcStreamReader::new(cursor).finish()?.lazy(),
+        other => bail!("{other:?} can't be read from memory."),
+    })
+}
+
+pub fn read_file(file_name: PathBuf, options: &ReadOptions) -> Result<LazyFrame> {
+    let file_type = file_type(&file_name, options)?;
+    if options.hdu.is_some() && !matches!(file_type, FileType::Fits) {
+        bail!("--hdu only applies to FITS files; {file_name:?} is not one.");
+    }
+    if options.explode_vectors && !matches!(file_type, FileType::Fits) {
+        bail!("--explode-vectors only applies to FITS files; {file_name:?} is not one.");
+    }
+    if options.csv != CsvOptions::default() && !matches!(file_type, FileType::Csv | FileType::Ecsv) {
+        bail!("The csv options only apply to csv files; {file_name:?} is not one.");
+    }
+    if let Some(bytes) = &options.stdin {
+        return read_stdin_bytes(bytes, file_type, options);
+    }
+    match file_type {
+        FileType::Csv | FileType::Ecsv => Ok(read_csv_file(file_name, &options.csv)?),
         FileType::Parquet => Ok(read_parquet_file(file_name)?),
-        FileType::Fits => Ok(read_fits_file(&file_name)?),
+        FileType::Fits => Ok(read_fits_file(&file_name, options)?),
+        FileType::Ipc => Ok(read_ipc_file(file_name)?),
+        FileType::Json | FileType::NdJson => Ok(read_json_file(file_name, file_type)?),
+        FileType::VoTable => Ok(read_votable_file(&file_name)?),
+        FileType::Ipac => Ok(read_ipac_file(&file_name)?),
     }
 }
 
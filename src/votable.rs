@@ -0,0 +1,909 @@
+//! Reading and writing IVOA VOTables.
+//!
+//! The first TABLE in the file is read. Its FIELDs give the columns:
+//!
+//! | datatype | dtype |
+//! | --- | --- |
+//! | `boolean` `bit` | Boolean |
+//! | `unsignedByte` `short` `int` `long` | UInt8, Int16, Int32, Int64 |
+//! | `float` `double` | Float32, Float64 |
+//! | `floatComplex` `doubleComplex` | Struct of `re`/`im` Float32 or Float64 |
+//! | `char` `unicodeChar` | String, whatever the arraysize |
+//!
+//! Any other arraysize gives an Array column, nested like a FITS `TDIMn` for
+//! `3x2`, or a List column when it is variable (`*`, `10*`). Rows can be
+//! serialised as TABLEDATA, BINARY or BINARY2 (with its per-row null flags).
+//! Empty cells, integers equal to the FIELD's `<VALUES null="...">` and NaN are
+//! read as nulls. Each FIELD's unit, ucd and DESCRIPTION are kept the same way
+//! as a FITS column's TUNITn, TUCDn and TCOMMn.
+//!
+//! Written VOTables use TABLEDATA, with nulls as empty cells. Null elements of
+//! array columns are NaN for floats, `?` for booleans, and for integers a value
+//! the column doesn't otherwise hold, given as the FIELD's `<VALUES null>`.
+//! VOTable has no unsigned 64-bit type, so UInt64 columns are written as `long`
+//! and refused if they hold a value above `i64::MAX`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use polars::prelude::*;
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::Path;
+
+use crate::compression::OutputFile;
+use crate::fits::FitsMetadata;
+use crate::fits_writer::storable_dtype;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Datatype {
+    Boolean,
+    Bit,
+    UnsignedByte,
+    Short,
+    Int,
+    Long,
+    Char,
+    UnicodeChar,
+    Float,
+    Double,
+    FloatComplex,
+    DoubleComplex,
+}
+
+impl Datatype {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "boolean" => Datatype::Boolean,
+            "bit" => Datatype::Bit,
+            "unsignedByte" => Datatype::UnsignedByte,
+            "short" => Datatype::Short,
+            "int" => Datatype::Int,
+            "long" => Datatype::Long,
+            "char" => Datatype::Char,
+            "unicodeChar" => Datatype::UnicodeChar,
+            "float" => Datatype::Float,
+            "double" => Datatype::Double,
+            "floatComplex" => Datatype::FloatComplex,
+            "doubleComplex" => Datatype::DoubleComplex,
+            other => bail!("unknown VOTable datatype '{other}'"),
+        })
+    }
+
+    // Bytes per element in a BINARY stream; bits are packed and handled apart.
+    fn size(self) -> usize {
+        match self {
+            Datatype::Bit => 0,
+            Datatype::Boolean | Datatype::UnsignedByte | Datatype::Char => 1,
+            Datatype::Short | Datatype::UnicodeChar => 2,
+            Datatype::Int | Datatype::Float => 4,
+            Datatype::Long | Datatype::Double | Datatype::FloatComplex => 8,
+            Datatype::DoubleComplex => 16,
+        }
+    }
+
+    fn is_text(self) -> bool {
+        matches!(self, Datatype::Char | Datatype::UnicodeChar)
+    }
+}
+
+enum Shape {
+    Scalar,
+    /// Dimensions with the first varying fastest, as in `arraysize="3x2"`.
+    Fixed(Vec<usize>),
+    Variable,
+}
+
+struct Field {
+    name: String,
+    datatype: Datatype,
+    arraysize: Option<String>,
+    unit: Option<String>,
+    ucd: Option<String>,
+    description: Option<String>,
+    null: Option<i64>,
+}
+
+impl Field {
+    fn shape(&self) -> Result<Shape> {
+        let Some(arraysize) = &self.arraysize else {
+            return Ok(Shape::Scalar);
+        };
+        if self.datatype.is_text() || arraysize.contains('*') {
+            return Ok(if self.datatype.is_text() {
+                Shape::Scalar
+            } else {
+                Shape::Variable
+            });
+        }
+        let dims = arraysize
+            .split('x')
+            .map(|n| n.trim().parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("FIELD '{}' has a bad arraysize '{arraysize}'", self.name))?;
+        Ok(if dims == [1] {
+            Shape::Scalar
+        } else {
+            Shape::Fixed(dims)
+        })
+    }
+
+    // Characters in a char field's cell, or `None` when the arraysize is
+    // variable. A string array (`10x3`) is read as one string of every character.
+    fn string_length(&self) -> Option<usize> {
+        match &self.arraysize {
+            None => Some(1),
+            Some(size) => size
+                .split('x')
+                .map(|n| n.trim().parse::<usize>().ok())
+                .product(),
+        }
+    }
+
+    fn described(&self) -> Vec<(String, String)> {
+        [
+            ("unit", &self.unit),
+            ("ucd", &self.ucd),
+            ("description", &self.description),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let value = value.as_ref()?.trim();
+            (!value.is_empty()).then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+    }
+}
+
+/// Cell values of one column, flattened when the cells are arrays.
+enum Cells {
+    Bool(Vec<Option<bool>>),
+    Int(Vec<Option<i64>>),
+    Float(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+    Complex(Vec<Option<(f64, f64)>>),
+}
+
+impl Cells {
+    fn new(datatype: Datatype) -> Self {
+        match datatype {
+            Datatype::Boolean | Datatype::Bit => Cells::Bool(Vec::new()),
+            Datatype::UnsignedByte | Datatype::Short | Datatype::Int | Datatype::Long => {
+                Cells::Int(Vec::new())
+            }
+            Datatype::Float | Datatype::Double => Cells::Float(Vec::new()),
+            Datatype::Char | Datatype::UnicodeChar => Cells::Text(Vec::new()),
+            Datatype::FloatComplex | Datatype::DoubleComplex => Cells::Complex(Vec::new()),
+        }
+    }
+
+    fn push_null(&mut self) {
+        match self {
+            Cells::Bool(v) => v.push(None),
+            Cells::Int(v) => v.push(None),
+            Cells::Float(v) => v.push(None),
+            Cells::Text(v) => v.push(None),
+            Cells::Complex(v) => v.push(None),
+        }
+    }
+
+    // Appends the elements written in a TABLEDATA cell and returns how many
+    // there were.
+    fn push_text(&mut self, field: &Field, text: &str) -> Result<usize> {
+        let bad = |token: &str| anyhow!("'{token}' is not a valid {}", field_kind(field));
+        let before = self.len();
+        match self {
+            Cells::Bool(v) if field.datatype == Datatype::Bit => v.extend(
+                text.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| Some(c == '1')),
+            ),
+            Cells::Bool(v) => {
+                for token in text.split_whitespace() {
+                    v.push(match token {
+                        "T" | "t" | "1" | "true" | "TRUE" | "True" => Some(true),
+                        "F" | "f" | "0" | "false" | "FALSE" | "False" => Some(false),
+                        "?" => None,
+                        _ => return Err(bad(token)),
+                    });
+                }
+            }
+            Cells::Int(v) => {
+                for token in text.split_whitespace() {
+                    let value = match token.strip_prefix("0x").or(token.strip_prefix("0X")) {
+                        Some(hex) => i64::from_str_radix(hex, 16),
+                        None => token.strip_prefix('+').unwrap_or(token).parse(),
+                    }
+                    .map_err(|_| bad(token))?;
+                    v.push((field.null != Some(value)).then_some(value));
+                }
+            }
+            Cells::Float(v) => {
+                for token in text.split_whitespace() {
+                    let value: f64 = token.parse().map_err(|_| bad(token))?;
+                    v.push((!value.is_nan()).then_some(value));
+                }
+            }
+            Cells::Text(v) => v.push(Some(text.to_string())),
+            Cells::Complex(v) => {
+                let tokens: Vec<&str> = text.split_whitespace().collect();
+                for pair in tokens.chunks(2) {
+                    let [re, im] = pair else {
+                        return Err(bad(pair[0]));
+                    };
+                    let re: f64 = re.parse().map_err(|_| bad(re))?;
+                    let im: f64 = im.parse().map_err(|_| bad(im))?;
+                    v.push((!re.is_nan() && !im.is_nan()).then_some((re, im)));
+                }
+            }
+        }
+        Ok(self.len() - before)
+    }
+
+    // Appends `count` elements of a BINARY stream; bits are `count` bits rather
+    // than bytes and strings are one element of `count` characters.
+    fn push_binary(&mut self, field: &Field, bytes: &[u8], count: usize) {
+        let size = field.datatype.size();
+        match self {
+            Cells::Bool(v) if field.datatype == Datatype::Bit => {
+                v.extend((0..count).map(|i| Some(bytes[i / 8] & (0x80 >> (i % 8)) != 0)))
+            }
+            Cells::Bool(v) => v.extend(bytes.iter().map(|b| match b {
+                b'T' | b't' | b'1' => Some(true),
+                b'F' | b'f' | b'0' => Some(false),
+                _ => None,
+            })),
+            Cells::Int(v) => v.extend(bytes.chunks_exact(size).map(|chunk| {
+                let value = match field.datatype {
+                    Datatype::UnsignedByte => i64::from(chunk[0]),
+                    Datatype::Short => i64::from(i16::from_be_bytes(chunk.try_into().unwrap())),
+                    Datatype::Int => i64::from(i32::from_be_bytes(chunk.try_into().unwrap())),
+                    _ => i64::from_be_bytes(chunk.try_into().unwrap()),
+                };
+                (field.null != Some(value)).then_some(value)
+            })),
+            Cells::Float(v) => v.extend(bytes.chunks_exact(size).map(|chunk| {
+                let value = if size == 4 {
+                    f64::from(f32::from_be_bytes(chunk.try_into().unwrap()))
+                } else {
+                    f64::from_be_bytes(chunk.try_into().unwrap())
+                };
+                (!value.is_nan()).then_some(value)
+            })),
+            Cells::Text(v) => {
+                let text = if field.datatype == Datatype::UnicodeChar {
+                    let units: Vec<u16> = bytes
+                        .chunks_exact(2)
+                        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                        .collect();
+                    String::from_utf16_lossy(&units)
+                } else {
+                    String::from_utf8_lossy(bytes).into_owned()
+                };
+                v.push(Some(text.trim_end_matches(['\0', ' ']).to_string()));
+            }
+            Cells::Complex(v) => v.extend(bytes.chunks_exact(size).map(|chunk| {
+                let half = size / 2;
+                let part = |bytes: &[u8]| {
+                    if half == 4 {
+                        f64::from(f32::from_be_bytes(bytes.try_into().unwrap()))
+                    } else {
+                        f64::from_be_bytes(bytes.try_into().unwrap())
+                    }
+                };
+                let (re, im) = (part(&chunk[..half]), part(&chunk[half..]));
+                (!re.is_nan() && !im.is_nan()).then_some((re, im))
+            })),
+        }
+    }
+
+    // Marks the last `count` elements as null, for BINARY2 null flags.
+    fn null_last(&mut self, count: usize) {
+        fn clear<T>(v: &mut [Option<T>], count: usize) {
+            let len = v.len();
+            v[len - count..].iter_mut().for_each(|x| *x = None);
+        }
+        match self {
+            Cells::Bool(v) => clear(v, count),
+            Cells::Int(v) => clear(v, count),
+            Cells::Float(v) => clear(v, count),
+            Cells::Text(v) => clear(v, count),
+            Cells::Complex(v) => clear(v, count),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Cells::Bool(v) => v.len(),
+            Cells::Int(v) => v.len(),
+            Cells::Float(v) => v.len(),
+            Cells::Text(v) => v.len(),
+            Cells::Complex(v) => v.len(),
+        }
+    }
+
+    fn into_series(self, datatype: Datatype) -> Result<Series> {
+        let name = PlSmallStr::EMPTY;
+        Ok(match self {
+            Cells::Bool(v) => Series::new(name, v),
+            Cells::Int(v) => Series::new(name, v).cast(&match datatype {
+                Datatype::UnsignedByte => DataType::UInt8,
+                Datatype::Short => DataType::Int16,
+                Datatype::Int => DataType::Int32,
+                _ => DataType::Int64,
+            })?,
+            Cells::Float(v) if datatype == Datatype::Float => {
+                Series::new(name, v).cast(&DataType::Float32)?
+            }
+            Cells::Float(v) => Series::new(name, v),
+            Cells::Text(v) => Series::new(name, v),
+            Cells::Complex(v) => {
+                let part = |pick: fn(&(f64, f64)) -> f64, part_name: &str| {
+                    let values: Vec<Option<f64>> =
+                        v.iter().map(|cell| cell.as_ref().map(pick)).collect();
+                    let series = Series::new(part_name.into(), values);
+                    if datatype == Datatype::FloatComplex {
+                        series.cast(&DataType::Float32)
+                    } else {
+                        Ok(series)
+                    }
+                };
+                let (re, im) = (part(|c| c.0, "re")?, part(|c| c.1, "im")?);
+                StructChunked::from_series(name, re.len(), [re, im].iter())?.into_series()
+            }
+        })
+    }
+}
+
+fn field_kind(field: &Field) -> String {
+    format!("value for FIELD '{}'", field.name)
+}
+
+/// One FIELD's cells as they are read, row by row.
+struct ColumnBuilder {
+    cells: Cells,
+    /// Elements per row for array fields; `None` marks a null row.
+    lengths: Vec<Option<usize>>,
+}
+
+impl ColumnBuilder {
+    fn new(field: &Field) -> Self {
+        Self {
+            cells: Cells::new(field.datatype),
+            lengths: Vec::new(),
+        }
+    }
+
+    fn push_text(&mut self, field: &Field, shape: &Shape, text: &str) -> Result<()> {
+        match shape {
+            Shape::Scalar if text.trim().is_empty() => self.cells.push_null(),
+            Shape::Scalar if field.datatype.is_text() => {
+                self.cells.push_text(field, text)?;
+            }
+            Shape::Scalar => {
+                if self.cells.push_text(field, text)? != 1 {
+                    bail!("'{}' is not a single {}", text.trim(), field_kind(field));
+                }
+            }
+            Shape::Fixed(dims) => {
+                let n: usize = dims.iter().product();
+                let count = self.cells.push_text(field, text)?;
+                if count > n {
+                    bail!("FIELD '{}' has more than {n} elements in a row", field.name);
+                }
+                (count..n).for_each(|_| self.cells.push_null());
+            }
+            Shape::Variable => {
+                let count = self.cells.push_text(field, text)?;
+                self.lengths.push(Some(count));
+            }
+        }
+        Ok(())
+    }
+
+    fn into_series(self, field: &Field, shape: &Shape, rows: usize) -> Result<Series> {
+        let name: PlSmallStr = field.name.as_str().into();
+        let flat = self.cells.into_series(field.datatype)?;
+        Ok(match shape {
+            Shape::Scalar => flat.with_name(name),
+            Shape::Fixed(dims) => {
+                let shape: Vec<ReshapeDimension> = std::iter::once(rows)
+                    .chain(dims.iter().rev().copied())
+                    .map(|n| ReshapeDimension::new(n as i64))
+                    .collect();
+                flat.reshape_array(&shape)?.with_name(name)
+            }
+            Shape::Variable => {
+                let mut offset = 0;
+                let rows: Vec<Option<Series>> = self
+                    .lengths
+                    .iter()
+                    .map(|length| {
+                        length.map(|length| {
+                            let row = flat.slice(offset as i64, length);
+                            offset += length;
+                            row
+                        })
+                    })
+                    .collect();
+                if rows.is_empty() {
+                    Series::new_empty(name, &DataType::List(Box::new(flat.dtype().clone())))
+                } else {
+                    let mut list = Series::new(name, rows);
+                    // A column that is null in every row has no element type to infer.
+                    if list.dtype() != &DataType::List(Box::new(flat.dtype().clone())) {
+                        list = list.cast(&DataType::List(Box::new(flat.dtype().clone())))?;
+                    }
+                    list
+                }
+            }
+        })
+    }
+}
+
+struct VoTable {
+    name: Option<String>,
+    fields: Vec<Field>,
+    columns: Vec<ColumnBuilder>,
+    rows: usize,
+}
+
+fn attribute(element: &BytesStart, key: &str) -> Result<Option<String>> {
+    Ok(match element.try_get_attribute(key)? {
+        Some(value) => Some(value.unescape_value()?.into_owned()),
+        None => None,
+    })
+}
+
+fn parse_field(element: &BytesStart) -> Result<Field> {
+    let name = attribute(element, "name")?
+        .or(attribute(element, "ID")?)
+        .ok_or_else(|| anyhow!("a FIELD has no name"))?;
+    let datatype =
+        attribute(element, "datatype")?.ok_or_else(|| anyhow!("FIELD '{name}' has no datatype"))?;
+    Ok(Field {
+        datatype: Datatype::parse(&datatype)?,
+        arraysize: attribute(element, "arraysize")?,
+        unit: attribute(element, "unit")?,
+        ucd: attribute(element, "ucd")?,
+        description: None,
+        null: None,
+        name,
+    })
+}
+
+// Reads the FIELDs of the first TABLE, and its rows too when `with_data` is set.
+fn parse_votable(path: &Path, with_data: bool) -> Result<VoTable> {
+    let mut reader = Reader::from_reader(BufReader::new(File::open(path)?));
+    let mut buf = Vec::new();
+    let mut table = VoTable {
+        name: None,
+        fields: Vec::new(),
+        columns: Vec::new(),
+        rows: 0,
+    };
+    let mut shapes = Vec::new();
+    let mut in_table = false;
+    let mut in_field = false;
+    // Text of the element being read (DESCRIPTION, TD or STREAM).
+    let mut text: Option<String> = None;
+    let mut column = 0;
+    let mut binary2 = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                let empty = matches!(event, Event::Empty(_));
+                match e.local_name().as_ref() {
+                    b"TABLE" if table.fields.is_empty() && !in_table => {
+                        in_table = true;
+                        table.name = attribute(e, "name")?;
+                    }
+                    b"FIELD" if in_table => {
+                        table.fields.push(parse_field(e)?);
+                        in_field = !empty;
+                    }
+                    b"VALUES" if in_field => {
+                        let field = table.fields.last_mut().expect("inside a FIELD");
+                        if let Some(null) = attribute(e, "null")? {
+                            field.null = null.trim().parse().ok();
+                        }
+                    }
+                    b"DESCRIPTION" if in_field && !empty => text = Some(String::new()),
+                    b"DATA" if in_table => {
+                        if !with_data {
+                            break;
+                        }
+                        for field in &table.fields {
+                            shapes.push(field.shape()?);
+                            table.columns.push(ColumnBuilder::new(field));
+                        }
+                    }
+                    b"TR" if in_table => column = 0,
+                    b"TD" if in_table => {
+                        text = Some(String::new());
+                        if empty {
+                            text = None;
+                            push_cell(&mut table, &shapes, &mut column, "")?;
+                        }
+                    }
+                    b"BINARY" if in_table => binary2 = false,
+                    b"BINARY2" if in_table => binary2 = true,
+                    b"STREAM" if in_table => {
+                        if attribute(e, "href")?.is_some() {
+                            bail!("VOTable data in an external stream isn't supported");
+                        }
+                        text = Some(String::new());
+                    }
+                    b"FITS" if in_table => {
+                        bail!("FITS-serialised VOTable data isn't supported; read the FITS file itself")
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(e) => {
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&e.xml_content()?);
+                }
+            }
+            Event::CData(e) => {
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&e.decode()?);
+                }
+            }
+            Event::GeneralRef(e) => {
+                if let Some(text) = text.as_mut() {
+                    match e.resolve_char_ref()? {
+                        Some(c) => text.push(c),
+                        None => text.push_str(&unescape(&format!("&{};", e.decode()?))?),
+                    }
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"FIELD" => in_field = false,
+                b"DESCRIPTION" if in_field => {
+                    let field = table.fields.last_mut().expect("inside a FIELD");
+                    field.description = text.take();
+                }
+                b"TD" => {
+                    if let Some(cell) = text.take() {
+                        push_cell(&mut table, &shapes, &mut column, &cell)?;
+                    }
+                }
+                b"TR" => {
+                    while column < table.fields.len() {
+                        push_cell(&mut table, &shapes, &mut column, "")?;
+                    }
+                    table.rows += 1;
+                }
+                b"STREAM" => {
+                    let encoded: String = text.take().unwrap_or_default();
+                    let encoded: String = encoded.split_whitespace().collect();
+                    let bytes = BASE64_STANDARD
+                        .decode(encoded)
+                        .context("the BINARY stream is not valid base64")?;
+                    read_stream(&mut table, &shapes, &bytes, binary2)?;
+                }
+                b"TABLE" if in_table => break,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if table.fields.is_empty() {
+        bail!("{}: no TABLE with FIELDs found", path.display());
+    }
+    Ok(table)
+}
+
+fn push_cell(table: &mut VoTable, shapes: &[Shape], column: &mut usize, text: &str) -> Result<()> {
+    let Some(field) = table.fields.get(*column) else {
+        bail!(
+            "row {} has more cells than there are FIELDs",
+            table.rows + 1
+        );
+    };
+    table.columns[*column]
+        .push_text(field, &shapes[*column], text)
+        .with_context(|| format!("row {}", table.rows + 1))?;
+    *column += 1;
+    Ok(())
+}
+
+// Decodes every row of a BINARY or BINARY2 stream.
+fn read_stream(table: &mut VoTable, shapes: &[Shape], bytes: &[u8], binary2: bool) -> Result<()> {
+    let truncated = || anyhow!("the BINARY stream ends in the middle of a row");
+    let flag_bytes = if binary2 {
+        table.fields.len().div_ceil(8)
+    } else {
+        0
+    };
+    let mut at = 0;
+    while at < bytes.len() {
+        let flags = bytes.get(at..at + flag_bytes).ok_or_else(truncated)?;
+        at += flag_bytes;
+        for (index, (field, shape)) in table.fields.iter().zip(shapes).enumerate() {
+            let is_null = binary2 && flags[index / 8] & (0x80 >> (index % 8)) != 0;
+            let mut take = |len: usize| {
+                let taken = bytes.get(at..at + len).ok_or_else(truncated);
+                at += len;
+                taken
+            };
+            let variable = match shape {
+                Shape::Variable => true,
+                Shape::Scalar if field.datatype.is_text() => field.string_length().is_none(),
+                _ => false,
+            };
+            let count = if variable {
+                u32::from_be_bytes(take(4)?.try_into().unwrap()) as usize
+            } else if field.datatype.is_text() {
+                field.string_length().unwrap_or(1)
+            } else {
+                match shape {
+                    Shape::Fixed(dims) => dims.iter().product(),
+                    _ => 1,
+                }
+            };
+            let len = if field.datatype == Datatype::Bit {
+                count.div_ceil(8)
+            } else {
+                count * field.datatype.size()
+            };
+            let cell = take(len)?;
+
+            let column = &mut table.columns[index];
+            let pushed = if field.datatype.is_text() { 1 } else { count };
+            column.cells.push_binary(field, cell, count);
+            if is_null {
+                column.cells.null_last(pushed);
+            }
+            if let Shape::Variable = shape {
+                column.lengths.push((!is_null).then_some(count));
+            }
+        }
+        table.rows += 1;
+    }
+    Ok(())
+}
+
+pub fn read_votable_file(path: &Path) -> Result<LazyFrame> {
+    let table = parse_votable(path, true)?;
+    let rows = table.rows;
+    let columns = table
+        .columns
+        .into_iter()
+        .zip(&table.fields)
+        .map(|(column, field)| {
+            let shape = field.shape()?;
+            Ok(column.into_series(field, &shape, rows)?.into())
+        })
+        .collect::<Result<Vec<Column>>>()?;
+    Ok(DataFrame::new(rows, columns)?.lazy())
+}
+
+/// Table name and FIELD units, UCDs and descriptions, to carry into another format.
+pub fn read_votable_metadata(path: &Path) -> Result<FitsMetadata> {
+    let table = parse_votable(path, false)?;
+    Ok(FitsMetadata {
+        extname: table.name,
+        header: Vec::new(),
+        columns: table
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), field.described()))
+            .filter(|(_, described)| !described.is_empty())
+            .collect(),
+    })
+}
+
+// The datatype and arraysize a column is written with. Anything without a
+// VOTable equivalent has already been cast to a string.
+fn field_type(dtype: &DataType) -> Result<(&'static str, Option<String>)> {
+    Ok(match dtype {
+        DataType::Boolean => ("boolean", None),
+        DataType::UInt8 => ("unsignedByte", None),
+        DataType::Int8 | DataType::Int16 => ("short", None),
+        DataType::UInt16 | DataType::Int32 => ("int", None),
+        DataType::UInt32 | DataType::Int64 | DataType::UInt64 => ("long", None),
+        DataType::Float32 => ("float", None),
+        DataType::Float64 => ("double", None),
+        DataType::String => ("char", Some("*".to_string())),
+        DataType::Struct(fields) if is_complex(fields) => match fields[0].dtype() {
+            DataType::Float32 => ("floatComplex", None),
+            _ => ("doubleComplex", None),
+        },
+        DataType::List(inner) => (field_type(inner)?.0, Some("*".to_string())),
+        DataType::Array(..) => {
+            let mut dims = Vec::new();
+            let mut leaf = dtype;
+            while let DataType::Array(inner, width) = leaf {
+                dims.push(width.to_string());
+                leaf = inner;
+            }
+            dims.reverse();
+            (field_type(leaf)?.0, Some(dims.join("x")))
+        }
+        other => bail!("columns of type {other} can't be written to a VOTable"),
+    })
+}
+
+fn is_complex(fields: &[polars::prelude::Field]) -> bool {
+    fields.len() == 2
+        && fields[0].name() == "re"
+        && fields[1].name() == "im"
+        && fields.iter().all(|f| f.dtype().is_float())
+}
+
+// TABLEDATA text of one cell; nulls and NaN are left empty, and null array
+// elements are written as `null`.
+fn cell_text(value: AnyValue, null: &str) -> String {
+    match value {
+        AnyValue::Null => String::new(),
+        AnyValue::Boolean(b) => if b { "T" } else { "F" }.to_string(),
+        AnyValue::Float32(f) if f.is_nan() => String::new(),
+        AnyValue::Float64(f) if f.is_nan() => String::new(),
+        AnyValue::String(s) => escape(s).into_owned(),
+        AnyValue::StringOwned(s) => escape(s.as_str()).into_owned(),
+        AnyValue::List(s) | AnyValue::Array(s, _) => {
+            let elements: Vec<String> = s.iter().map(|v| element_text(v, null)).collect();
+            elements.join(" ")
+        }
+        AnyValue::Struct(..) | AnyValue::StructOwned(..) => {
+            let parts: Vec<String> = value._iter_struct_av().map(|v| element_text(v, null)).collect();
+            parts.join(" ")
+        }
+        other => other.to_string(),
+    }
+}
+
+fn element_text(value: AnyValue, null: &str) -> String {
+    match value {
+        AnyValue::Null => null.to_string(),
+        other => cell_text(other, null),
+    }
+}
+
+// The elements of a list or array column, however deeply nested, without its
+// null rows but with its null elements; any other column is returned as it is.
+fn elements(series: &Series) -> Result<Series> {
+    let mut values = series.clone();
+    while matches!(values.dtype(), DataType::List(_) | DataType::Array(..)) {
+        values = values.explode(ExplodeOptions {
+            empty_as_null: false,
+            keep_nulls: false,
+        })?;
+    }
+    Ok(values)
+}
+
+// Inside an array there is no empty cell to fall back on, so null elements are
+// written as NaN for floats and `?` for booleans. Integers have no null value
+// of their own, so a column with null elements gets a sentinel it doesn't
+// otherwise hold, declared as its FIELD's `<VALUES null="...">`. Returns the
+// null text and that sentinel.
+fn null_element(series: &Series, datatype: &str) -> Result<(String, Option<i64>)> {
+    let nested = matches!(series.dtype(), DataType::List(_) | DataType::Array(..));
+    let elements = elements(series)?;
+    Ok(match elements.dtype() {
+        DataType::Boolean => ("?".to_string(), None),
+        DataType::Struct(_) => ("NaN".to_string(), None),
+        dtype if dtype.is_float() => ("NaN".to_string(), None),
+        dtype if nested && dtype.is_integer() && elements.null_count() > 0 => {
+            let sentinel = match datatype {
+                "unsignedByte" => i64::from(u8::MAX),
+                "short" => i64::from(i16::MIN),
+                "int" => i64::from(i32::MIN),
+                _ => i64::MIN,
+            };
+            let values = elements.cast(&DataType::Int64)?;
+            if values.i64()?.iter().any(|v| v == Some(sentinel)) {
+                bail!("it has null array elements, but also holds {sentinel}, which would mark them");
+            }
+            (sentinel.to_string(), Some(sentinel))
+        }
+        _ => (String::new(), None),
+    })
+}
+
+// UInt64 is written as `long`, which can't hold the top half of its range.
+fn check_long(series: &Series) -> Result<()> {
+    let elements = elements(series)?;
+    if let Ok(values) = elements.u64() {
+        if let Some(max) = values.max().filter(|&max| max > i64::MAX as u64) {
+            bail!("it holds {max}, which is too large for a VOTable long");
+        }
+    }
+    Ok(())
+}
+
+/// Writes the frame as a VOTable with TABLEDATA rows, describing each FIELD
+/// with the unit, ucd and description in `metadata`.
+pub fn write_votable(
+    lazy_frame: &LazyFrame,
+    output_path: &Path,
+    metadata: &FitsMetadata,
+) -> Result<()> {
+    let df = lazy_frame.clone().collect()?;
+    let columns = df
+        .columns()
+        .iter()
+        .map(|column| {
+            let series = column.as_materialized_series();
+            Ok(series.cast(&storable_dtype(series.dtype()))?)
+        })
+        .collect::<Result<Vec<Series>>>()?;
+
+    let mut out = OutputFile::create(output_path)?;
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">"#
+    )?;
+    writeln!(out, "<RESOURCE>")?;
+    match &metadata.extname {
+        Some(name) => writeln!(out, r#"<TABLE name="{}">"#, escape(name.as_str()))?,
+        None => writeln!(out, "<TABLE>")?,
+    }
+    let mut nulls = Vec::with_capacity(columns.len());
+    for series in &columns {
+        let name = series.name().as_str();
+        let (datatype, arraysize) =
+            field_type(series.dtype()).with_context(|| format!("column '{name}'"))?;
+        check_long(series).with_context(|| format!("column '{name}'"))?;
+        let (null, sentinel) =
+            null_element(series, datatype).with_context(|| format!("column '{name}'"))?;
+        nulls.push(null);
+        write!(
+            out,
+            r#"<FIELD name="{}" datatype="{datatype}""#,
+            escape(name)
+        )?;
+        if let Some(arraysize) = arraysize {
+            write!(out, r#" arraysize="{arraysize}""#)?;
+        }
+        let described = metadata.column(name).unwrap_or_default();
+        let value = |key: &str| {
+            described
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| escape(v.as_str()).into_owned())
+        };
+        for key in ["unit", "ucd"] {
+            if let Some(value) = value(key) {
+                write!(out, r#" {key}="{value}""#)?;
+            }
+        }
+        let mut children = String::new();
+        if let Some(description) = value("description") {
+            children.push_str(&format!("<DESCRIPTION>{description}</DESCRIPTION>\n"));
+        }
+        if let Some(sentinel) = sentinel {
+            children.push_str(&format!("<VALUES null=\"{sentinel}\"/>\n"));
+        }
+        if children.is_empty() {
+            writeln!(out, "/>")?;
+        } else {
+            writeln!(out, ">\n{children}</FIELD>")?;
+        }
+    }
+    writeln!(out, "<DATA>\n<TABLEDATA>")?;
+    for row in 0..df.height() {
+        write!(out, "<TR>")?;
+        for (series, null) in columns.iter().zip(&nulls) {
+            write!(out, "<TD>{}</TD>", cell_text(series.get(row)?, null))?;
+        }
+        writeln!(out, "</TR>")?;
+    }
+    writeln!(
+        out,
+        "</TABLEDATA>\n</DATA>\n</TABLE>\n</RESOURCE>\n</VOTABLE>"
+    )?;
+    out.finish()?;
+    Ok(())
+}
@@ -1,12 +1,384 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use polars::prelude::*;
-use std::{fs::File, path::PathBuf};
+use polars_parquet::write::{
+    get_dtype_encoding, row_group_iter, schema_to_metadata_key, BrotliLevel, Encoding, FileWriter,
+    GzipLevel, KeyValue, StatisticsOptions, Version, WriteOptions, ZstdLevel,
+};
+use std::io::Write;
+use std::{fs::File, path::{Path, PathBuf}};
 
-pub fn write_parquet(lazy_frame: &LazyFrame, output_path: &PathBuf) -> Result<()> {
-    let file = File::create(output_path)?;
-    let mut df = lazy_frame.clone().collect().unwrap();
+use crate::compression::{Compression, OutputFile};
+use crate::ecsv::ecsv_frame;
+use crate::fits::FitsMetadata;
+use crate::maml::trim_maml_fields;
+use crate::printer::nested_as_text;
 
-    ParquetWriter::new(file).finish(&mut df)?;
+// Parquet and Arrow IPC compress their own pages, so a `.gz` or similar
+// extension on them is a mistake rather than a request.
+fn refuse_compression(output_path: &Path, format: &str) -> Result<()> {
+    if let Some(compression) = Compression::from_extension(output_path) {
+        bail!("{format} files are compressed internally and can't be written as {compression:?}; drop the extension from {output_path:?}.");
+    }
+    Ok(())
+}
+
+/// An output written under a hidden temporary name in the same directory, and
+/// renamed into place by `commit` once it is complete. If writing fails, or
+/// `commit` is never reached, the temporary file is removed and whatever was
+/// at the output path before is left as it was.
+pub struct PendingOutput {
+    temp: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl PendingOutput {
+    pub fn new(path: &Path) -> Self {
+        // The name keeps its extensions, since the writers look at them to
+        // decide on compression.
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = path.with_file_name(format!(".dog-{}.{name}", std::process::id()));
+        PendingOutput {
+            temp,
+            path: path.to_path_buf(),
+            committed: false,
+        }
+    }
+
+    /// Where the output is written until it is committed.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        std::fs::rename(&self.temp, &self.path)
+            .with_context(|| format!("{:?} could not be moved into place", self.path))?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PendingOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// How parquet files are written, from the command line or the config file.
+/// Anything left unset keeps the polars default.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct ParquetOptions {
+    /// `uncompressed`, `snappy`, `gzip`, `brotli`, `zstd` or `lz4`.
+    pub compression: Option<String>,
+    pub compression_level: Option<i64>,
+    pub row_group_size: Option<usize>,
+    /// In bytes.
+    pub data_page_size: Option<usize>,
+    pub statistics: Option<bool>,
+    /// Dictionary-encode every string column (on), or write every column
+    /// plain (off).
+    pub dictionary: Option<bool>,
+}
+
+impl ParquetOptions {
+    /// These options, with whatever they leave unset taken from `defaults`.
+    /// A codec and its level go together: if these options name a codec, the
+    /// level of the one in `defaults` is not carried over to it.
+    pub fn or(self, defaults: ParquetOptions) -> Self {
+        let compression_level = match self.compression {
+            Some(_) => self.compression_level,
+            None => self.compression_level.or(defaults.compression_level),
+        };
+        ParquetOptions {
+            compression: self.compression.or(defaults.compression),
+            compression_level,
+            row_group_size: self.row_group_size.or(defaults.row_group_size),
+            data_page_size: self.data_page_size.or(defaults.data_page_size),
+            statistics: self.statistics.or(defaults.statistics),
+            dictionary: self.dictionary.or(defaults.dictionary),
+        }
+    }
+
+    fn compression(&self) -> Result<ParquetCompression> {
+        let codec = self.compression.as_deref().unwrap_or("zstd");
+        let level = self.compression_level;
+        let out_of_range = |level: i64| anyhow!("{level} is not a {codec} compression level.");
+        Ok(match codec {
+            "gzip" => ParquetCompression::Gzip(match level {
+                Some(level) => Some(GzipLevel::try_new(level.try_into().map_err(|_| out_of_range(level))?)?),
+                None => None,
+            }),
+            "brotli" => ParquetCompression::Brotli(match level {
+                Some(level) => Some(BrotliLevel::try_new(level.try_into().map_err(|_| out_of_range(level))?)?),
+                None => None,
+            }),
+            "zstd" => ParquetCompression::Zstd(match level {
+                Some(level) => Some(ZstdLevel::try_new(level.try_into().map_err(|_| out_of_range(level))?)?),
+                None => None,
+            }),
+            "uncompressed" | "snappy" | "lz4" if level.is_some() => {
+                bail!("{codec} has no compression levels; drop the compression level.")
+            }
+            "uncompressed" => ParquetCompression::Uncompressed,
+            "snappy" => ParquetCompression::Snappy,
+            "lz4" => ParquetCompression::Lz4Raw,
+            codec => bail!("'{codec}' is not a parquet compression; use uncompressed, snappy, gzip, brotli, zstd or lz4."),
+        })
+    }
+
+    fn write_options(&self, key_value: Vec<(String, String)>) -> Result<ParquetWriteOptions> {
+        Ok(ParquetWriteOptions {
+            compression: self.compression()?,
+            statistics: match self.statistics {
+                Some(false) => StatisticsOptions::empty(),
+                _ => StatisticsOptions::default(),
+            },
+            row_group_size: self.row_group_size,
+            data_page_size: self.data_page_size,
+            key_value_metadata: Some(KeyValueMetadata::from_static(key_value)),
+            ..ParquetWriteOptions::default()
+        })
+    }
+}
+
+// Sinks `lazy_frame` into `output_path` as `format` on the streaming engine,
+// so the output is written a batch at a time instead of the whole result
+// being collected first.
+fn stream_to(lazy_frame: &LazyFrame, output_path: &Path, format: FileWriteFormat) -> Result<()> {
+    let path = output_path
+        .to_str()
+        .with_context(|| format!("{output_path:?} is not valid UTF-8, so it can't be streamed to."))?;
+    let destination = SinkDestination::File {
+        target: SinkTarget::Path(PlRefPath::new(path)),
+    };
+    lazy_frame
+        .clone()
+        .sink(destination, format, UnifiedSinkArgs::default())?
+        .collect_with_engine(Engine::Streaming)?;
+    Ok(())
+}
+
+/// Writes a parquet file with `keywords` in its footer's keyword metadata and
+/// each column's unit, UCD and description from `metadata` on its Arrow field.
+pub fn write_parquet(
+    lazy_frame: &LazyFrame,
+    output_path: &Path,
+    metadata: &FitsMetadata,
+    keywords: Vec<(String, String)>,
+    options: &ParquetOptions,
+) -> Result<()> {
+    refuse_compression(output_path, "Parquet")?;
+    let schema = lazy_frame.clone().collect_schema()?;
+
+    // Polars fields carry no metadata, so the Arrow schema stored in the footer
+    // is built here with it and takes the place of the one polars would write.
+    let mut arrow_schema = schema.to_arrow(CompatLevel::newest());
+    for (name, field) in arrow_schema.iter_mut() {
+        if let Some(described) = metadata.column(name) {
+            field.metadata = Some(Arc::new(
+                described
+                    .iter()
+                    .map(|(key, value)| (key.into(), value.into()))
+                    .collect(),
+            ));
+        }
+    }
+    match options.dictionary {
+        None => {
+            let options = options.write_options(footer_keywords(&arrow_schema, keywords))?;
+            stream_to(lazy_frame, output_path, FileWriteFormat::Parquet(Arc::new(options)))
+        }
+        Some(dictionary) => write_encoded(
+            lazy_frame,
+            output_path,
+            arrow_schema,
+            &options.write_options(Vec::new())?,
+            keywords,
+            dictionary,
+        ),
+    }
+}
+
+/// `keywords` after the Arrow schema polars reads the columns' dtypes from.
+fn footer_keywords(arrow_schema: &ArrowSchema, keywords: Vec<(String, String)>) -> Vec<(String, String)> {
+    let arrow_key = schema_to_metadata_key(arrow_schema);
+    let mut key_value = vec![(arrow_key.key, arrow_key.value.unwrap_or_default())];
+    key_value.extend(keywords);
+    key_value
+}
+
+// Polars picks every column's encoding itself when it sinks a parquet file, so
+// `--dictionary` writes the collected table through polars-parquet's row group
+// writer instead. On, string columns are stored as categoricals, which are
+// always dictionary-encoded, and read back as strings; off, categoricals are
+// stored, and read back, as strings and every page is plain.
+fn write_encoded(
+    lazy_frame: &LazyFrame,
+    output_path: &Path,
+    mut arrow_schema: ArrowSchema,
+    options: &ParquetWriteOptions,
+    keywords: Vec<(String, String)>,
+    dictionary: bool,
+) -> Result<()> {
+    let mut df = lazy_frame.clone().collect()?;
+    let stored_as = |dtype: &DataType| match dtype {
+        DataType::String if dictionary => Some(DataType::from_categories(Categories::global())),
+        dtype if !dictionary && (dtype.is_categorical() || dtype.is_enum()) => Some(DataType::String),
+        _ => None,
+    };
+    let columns = df
+        .columns()
+        .iter()
+        .map(|column| match stored_as(column.dtype()) {
+            Some(dtype) => column.cast(&dtype),
+            None => Ok(column.clone()),
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+    df = DataFrame::new(df.height(), columns)?;
+    if !dictionary {
+        for (column, (_, field)) in df.columns().iter().zip(arrow_schema.iter_mut()) {
+            field.dtype = column.dtype().to_arrow(CompatLevel::newest());
+        }
+    }
+
+    // A categorical nested in a list can only be written as a dictionary.
+    let encodings: Vec<Vec<Encoding>> = df
+        .columns()
+        .iter()
+        .map(|column| {
+            let encodings = get_dtype_encoding(&column.dtype().to_arrow(CompatLevel::newest()));
+            if dictionary || column.dtype().contains_categoricals() {
+                encodings
+            } else {
+                vec![Encoding::Plain; encodings.len()]
+            }
+        })
+        .collect();
+    let write_options = WriteOptions {
+        statistics: options.statistics,
+        version: Version::V1,
+        compression: options.compression.into(),
+        data_page_size: options.data_page_size,
+    };
+    let key_value = footer_keywords(&arrow_schema, keywords);
+    let mut writer = FileWriter::try_new(File::create(output_path)?, arrow_schema, write_options)?;
+    let fields = writer.parquet_schema().fields().to_vec();
+    let group_rows = options.row_group_size.unwrap_or(512 * 512).max(1);
+    for offset in (0..df.height()).step_by(group_rows) {
+        let mut group = df.slice(offset as i64, group_rows);
+        group.rechunk_mut();
+        for batch in group.iter_chunks(CompatLevel::newest(), false) {
+            let columns = row_group_iter(batch, encodings.clone().into(), fields.clone(), write_options);
+            writer.write(group.height() as u64, columns)?;
+        }
+    }
+    writer.end(Some(
+        key_value
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value))
+            .collect(),
+    ))?;
+    Ok(())
+}
+
+/// The keyword metadata of the parquet file at `path`, to carry into a subset
+/// of it holding `columns`. A `maml` block keeps only the `fields` entries of
+/// those columns; the Arrow schema is left for the new file to write.
+pub fn carried_keywords(path: &Path, columns: &[&str]) -> Result<Vec<(String, String)>> {
+    let key_values = ParquetReader::new(File::open(path)?)
+        .get_metadata()?
+        .key_value_metadata()
+        .clone()
+        .unwrap_or_default();
+    Ok(key_values
+        .into_iter()
+        .filter(|kv| kv.key != "ARROW:schema")
+        .map(|kv| {
+            let value = kv.value.unwrap_or_default();
+            let value = match kv.key.as_str() {
+                "maml" => trim_maml_fields(&value, columns),
+                _ => value,
+            };
+            (kv.key, value)
+        })
+        .collect())
+}
+
+/// Writes an Arrow IPC file (Feather v2), which polars and pyarrow can scan lazily.
+pub fn write_ipc(lazy_frame: &LazyFrame, output_path: &Path) -> Result<()> {
+    refuse_compression(output_path, "Arrow IPC")?;
+    stream_to(lazy_frame, output_path, FileWriteFormat::Ipc(IpcWriterOptions::default()))
+}
+
+/// Writes a JSON array of row objects, or with `JsonFormat::JsonLines` one
+/// object per line. Struct columns become nested objects. Uncompressed
+/// newline-delimited JSON is streamed; an array is written in one go.
+pub fn write_json(lazy_frame: &LazyFrame, output_path: &Path, format: JsonFormat) -> Result<()> {
+    if matches!(format, JsonFormat::JsonLines) && Compression::from_extension(output_path).is_none() {
+        return stream_to(lazy_frame, output_path, FileWriteFormat::NDJson(NDJsonWriterOptions::default()));
+    }
+    let mut out = OutputFile::create(output_path)?;
+    let mut df = lazy_frame.clone().collect()?;
+
+    JsonWriter::new(&mut out)
+        .with_json_format(format)
+        .finish(&mut df)?;
+    out.finish()?;
 
     Ok(())
 }
+
+/// Writes comma-separated values with a header line. Nested columns are
+/// written as text, the same way `-d` prints them, which needs the whole
+/// table; otherwise uncompressed csv is streamed.
+pub fn write_csv(lazy_frame: &LazyFrame, output_path: &Path) -> Result<()> {
+    let schema = lazy_frame.clone().collect_schema()?;
+    let nested = schema.iter_values().any(|dtype| dtype.is_nested());
+    if !nested && Compression::from_extension(output_path).is_none() {
+        return stream_to(lazy_frame, output_path, FileWriteFormat::Csv(CsvWriterOptions::default()));
+    }
+    let mut out = OutputFile::create(output_path)?;
+    let mut df = nested_as_text(lazy_frame.clone().collect()?)?;
+
+    CsvWriter::new(&mut out).include_header(true).finish(&mut df)?;
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Writes an Astropy ECSV file: a YAML header giving each column's dtype and
+/// the unit and description in `metadata`, then comma-separated rows with
+/// nulls left empty.
+pub fn write_ecsv(lazy_frame: &LazyFrame, output_path: &Path, metadata: &FitsMetadata) -> Result<()> {
+    let (header, mut df) = ecsv_frame(lazy_frame.clone().collect()?, metadata)?;
+
+    let mut out = OutputFile::create(output_path)?;
+    out.write_all(header.as_bytes())?;
+    CsvWriter::new(&mut out).include_header(true).finish(&mut df)?;
+    out.finish()?;
+
+    Ok(())
+}
+
+/// Writes a converted FITS table or VOTable. The header cards, if there are any,
+/// go under the `fits_header` keyword, one card per line, and each column's unit,
+/// UCD and description become metadata on its Arrow field. A generated `maml`
+/// block is added if given.
+pub fn write_converted_fits(
+    lazy_frame: &LazyFrame,
+    output_path: &Path,
+    metadata: &FitsMetadata,
+    maml: Option<String>,
+    options: &ParquetOptions,
+) -> Result<()> {
+    let mut keywords = Vec::new();
+    if !metadata.header.is_empty() {
+        keywords.push(("fits_header".to_string(), metadata.header.join("\n")));
+    }
+    if let Some(maml) = maml {
+        keywords.push(("maml".to_string(), maml));
+    }
+    write_parquet(lazy_frame, output_path, metadata, keywords, options)
+}
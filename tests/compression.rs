@@ -0,0 +1,92 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::io::{Read, Write};
+use std::{fs, path::PathBuf};
+use tempfile::tempdir;
+
+const CSV: &str = "id,redshift\nxyz,0.1\nabc,0.2\n";
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn every_compression_is_read() {
+    let dir = tempdir().expect("create temp dir");
+    let files = [
+        ("cat.csv.gz", gzip(CSV.as_bytes())),
+        ("cat.csv.zst", zstd::encode_all(CSV.as_bytes(), 0).unwrap()),
+        ("cat.csv.bz2", {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(CSV.as_bytes()).unwrap();
+            encoder.finish().unwrap()
+        }),
+        ("cat.csv.xz", {
+            let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+            encoder.write_all(CSV.as_bytes()).unwrap();
+            encoder.finish().unwrap()
+        }),
+        // Found by its magic bytes alone.
+        ("pipeline_output", gzip(CSV.as_bytes())),
+    ];
+    for (name, bytes) in files {
+        let path = dir.path().join(name);
+        fs::write(&path, bytes).unwrap();
+        dog(&["-d"], &path)
+            .success()
+            .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+    }
+}
+
+#[test]
+fn compressed_parquet_converts_next_to_the_original() {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("test.parquet.gz");
+    fs::write(&path, gzip(&fs::read("tests/fixtures/test.parquet").unwrap())).unwrap();
+    dog(&["--convert", "--to", "json"], &path).success();
+    let converted = dir.path().join("test_converted.json");
+    dog(&["-c", "id,redshift", "-d"], &converted)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn outfile_is_compressed_by_its_extension() {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = PathBuf::from("tests/fixtures/test.parquet");
+    let out = dir.path().join("subset.csv.gz");
+    dog(&["-c", "id,redshift", "-o", out.to_str().unwrap()], &parquet).success();
+
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(&out).unwrap())
+        .read_to_string(&mut text)
+        .unwrap();
+    assert_eq!(text, CSV);
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn compressed_parquet_output_is_refused() {
+    let dir = tempdir().expect("create temp dir");
+    let out = dir.path().join("subset.parquet.gz");
+    dog(
+        &["-c", "id", "-o", out.to_str().unwrap()],
+        &PathBuf::from("tests/fixtures/test.parquet"),
+    )
+    .failure()
+    .stderr(predicate::str::contains("compressed internally"));
+}
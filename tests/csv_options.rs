@@ -0,0 +1,109 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+fn text_file(name: &str, text: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    fs::write(&path, text).expect("failed to write csv");
+    (dir, path)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn semicolons_split_columns() {
+    let (_dir, file) = text_file("export.csv", "id;redshift\nxyz;0.1\nabc;0.2\n");
+    dog(&["--separator", ";", "-d"], &file)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn tsv_files_split_on_tabs() {
+    let (_dir, file) = text_file("export.tsv", "id\tredshift\nxyz\t0.1\nabc\t0.2\n");
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn aligned_text_without_a_header() {
+    let (_dir, file) = text_file(
+        "aligned.txt",
+        "# written by the pipeline\n  xyz    0.1   \"NGC 224\"\n  abc    0.25  M31\n",
+    );
+    dog(
+        &["--separator", "whitespace", "--no-header", "--comment-prefix", "#", "-n"],
+        &file,
+    )
+    .success()
+    .stdout(predicate::eq("column_1\ncolumn_2\ncolumn_3\n"));
+    dog(
+        &["--separator", "whitespace", "--no-header", "--comment-prefix", "#", "-d"],
+        &file,
+    )
+    .success()
+    .stdout(predicate::eq("xyz 0.1 \"NGC 224\"\nabc 0.25 M31\n"));
+}
+
+#[test]
+fn hyphenated_comment_prefix() {
+    let (_dir, file) = text_file("query.csv", "-- exported from the archive\nid,mag\nxyz,17.5\n");
+    dog(&["--comment-prefix", "--", "-d"], &file)
+        .success()
+        .stdout(predicate::eq("xyz 17.5\n"));
+}
+
+#[test]
+fn null_tokens_and_skipped_rows() {
+    let (_dir, file) = text_file(
+        "photometry.csv",
+        "survey export\nversion 2\nid,mag\n007,-99\n012,NaN\n031,17.5\n",
+    );
+    dog(
+        &[
+            "--skip-rows",
+            "2",
+            "--null-values",
+            "-99,NaN",
+            "--schema-overrides",
+            "id:str,mag:f32",
+            "-d",
+        ],
+        &file,
+    )
+    .success()
+    .stdout(predicate::eq("007 \n012 \n031 17.5\n"));
+}
+
+#[test]
+fn quoting_can_be_turned_off() {
+    let (_dir, file) = text_file("log.csv", "id,note\n1,\"odd\n2,fine\n");
+    dog(&["--quote-char", "none", "-c", "note", "-d"], &file)
+        .success()
+        .stdout(predicate::eq("\"\"\"odd\"\nfine\n"));
+}
+
+#[test]
+fn csv_options_are_refused_for_other_formats() {
+    dog(&["--separator", ";"], &PathBuf::from("tests/fixtures/test.parquet"))
+        .failure()
+        .stderr(predicate::str::contains("only apply to csv files"));
+}
+
+#[test]
+fn bad_schema_overrides_are_reported() {
+    let (_dir, file) = text_file("export.csv", "id,redshift\nxyz,0.1\n");
+    dog(&["--schema-overrides", "id:text"], &file)
+        .failure()
+        .stderr(predicate::str::contains("Unknown column type 'text'"));
+}
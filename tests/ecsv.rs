@@ -0,0 +1,148 @@
+use assert_cmd::Command;
+use polars::prelude::{ParquetReader, SerReader};
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+const OBSERVATIONS: &str = r#"# %ECSV 1.0
+# ---
+# datatype:
+# - {name: id, datatype: int32}
+# - {name: flux, unit: mJy, datatype: float64, description: Peak flux}
+# - {name: flag, datatype: bool}
+# - {name: name, datatype: string}
+# meta: !!omap
+# - {TELESCOP: AAT}
+# - EXPTIME: 1200
+# - history: [first pass, second pass]
+# schema: astropy-2.0
+id flux flag name
+1 1.5 True M31
+2 2 False "NGC 224"
+"#;
+
+const VECTORS: &str = r#"# %ECSV 1.0
+# ---
+# delimiter: ','
+# datatype:
+# - {name: id, datatype: int64}
+# - {name: pos, datatype: string, subtype: 'float64[2]'}
+# - {name: samples, datatype: string, subtype: 'int64[null]'}
+id,pos,samples
+1,"[10.5,-3.0]","[1,2,3]"
+2,"[11.0,4.5]","[4]"
+"#;
+
+fn ecsv(name: &str, text: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    fs::write(&path, text).expect("failed to write ecsv");
+    (dir, path)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn stdout_of(args: &[&str], file: &PathBuf) -> String {
+    let output = dog(args, file).success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn declared_dtypes_are_used() {
+    // No extension, so the `# %ECSV` line is all there is to go on.
+    let (_dir, file) = ecsv("observations", OBSERVATIONS);
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("1 1.5 true M31\n2 2.0 false \"NGC 224\"\n"));
+    let schema = stdout_of(&["--schema"], &file);
+    assert!(schema.contains("Int32"), "{schema}");
+    assert!(schema.contains("Boolean"), "{schema}");
+}
+
+#[test]
+fn meta_is_read_by_the_keyword_commands() {
+    let (_dir, file) = ecsv("observations.ecsv", OBSERVATIONS);
+    dog(&["--list-keywords"], &file)
+        .success()
+        .stdout(predicate::eq("TELESCOP\nEXPTIME\nhistory\n"));
+    dog(&["-k", "EXPTIME"], &file)
+        .success()
+        .stdout(predicate::eq("1200\n"));
+    dog(&["-k", "history"], &file)
+        .success()
+        .stdout(predicate::eq("- first pass\n- second pass\n"));
+    dog(&["-k", "OBSERVER"], &file)
+        .failure()
+        .stderr(predicate::str::contains("not in the ECSV meta"));
+}
+
+#[test]
+fn subtypes_become_array_and_list_columns() {
+    let (dir, file) = ecsv("vectors.ecsv", VECTORS);
+    let schema = stdout_of(&["--schema"], &file);
+    assert!(schema.contains("Array"), "{schema}");
+    assert!(schema.contains("List"), "{schema}");
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("1 [10.5,-3.0] [1,2,3]\n2 [11.0,4.5] [4]\n"));
+
+    let out = dir.path().join("copy.ecsv");
+    dog(&["-c", "id,pos,samples", "-o", out.to_str().unwrap()], &file).success();
+    let text = fs::read_to_string(&out).unwrap();
+    assert!(text.contains("float64[2]"), "{text}");
+    assert!(text.contains("int64[null]"), "{text}");
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("1 [10.5,-3.0] [1,2,3]\n2 [11.0,4.5] [4]\n"));
+}
+
+#[test]
+fn outfile_writes_ecsv() {
+    let (dir, file) = ecsv("observations.ecsv", OBSERVATIONS);
+    let out = dir.path().join("bright.ecsv");
+    dog(&["-f", "flux < 2", "-o", out.to_str().unwrap()], &file).success();
+    let text = fs::read_to_string(&out).unwrap();
+    assert!(text.starts_with("# %ECSV 1.0\n# ---\n"), "{text}");
+    assert!(text.contains("unit: mJy"), "{text}");
+    assert!(text.contains("\nid,flux,flag,name\n1,1.5,True,M31\n"), "{text}");
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("1 1.5 true M31\n"));
+}
+
+#[test]
+fn convert_keeps_units_and_descriptions() {
+    let (dir, file) = ecsv("observations.ecsv", OBSERVATIONS);
+    dog(&["--convert"], &file).success();
+    let parquet = fs::File::open(dir.path().join("observations_converted.parquet")).unwrap();
+    let schema = ParquetReader::new(parquet).schema().unwrap();
+    let flux = schema.get("flux").unwrap().metadata.clone().unwrap();
+    assert_eq!(flux.get("unit").map(|v| v.as_str()), Some("mJy"));
+    assert_eq!(
+        flux.get("description").map(|v| v.as_str()),
+        Some("Peak flux")
+    );
+
+    dog(&["--convert", "--to", "ecsv"], &file)
+        .failure()
+        .stderr(predicate::str::contains("already an ECSV file"));
+}
+
+#[test]
+fn parquet_converts_to_ecsv() {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &parquet).unwrap();
+    dog(&["--convert", "--to", "ecsv"], &parquet).success();
+    let converted = dir.path().join("test_converted.ecsv");
+    dog(&["-c", "id,redshift", "-d"], &converted)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
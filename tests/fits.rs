@@ -0,0 +1,945 @@
+use assert_cmd::Command;
+use fitsio_pure_tsl::header::{serialize_header, Card};
+use fitsio_pure_tsl::primary::build_primary_header;
+use fitsio_pure_tsl::value::Value;
+use polars::prelude::{ParquetReader, SerReader};
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+use yaml_rust2::YamlLoader;
+
+fn card(keyword: &str, value: Value) -> Card {
+    let mut padded = [b' '; 8];
+    padded[..keyword.len()].copy_from_slice(keyword.as_bytes());
+    Card {
+        keyword: padded,
+        value: Some(value),
+        comment: None,
+    }
+}
+
+/// Header followed by the data segment padded to a whole 2880-byte block.
+fn hdu_bytes(cards: &[Card], data: &[u8]) -> Vec<u8> {
+    let mut out = serialize_header(cards).expect("failed to serialize header");
+    out.extend_from_slice(data);
+    out.resize(out.len() + (2880 - data.len() % 2880) % 2880, 0);
+    out
+}
+
+fn primary() -> Vec<u8> {
+    primary_with(&[])
+}
+
+/// A data-less primary HDU with `keywords` appended to its header.
+fn primary_with(keywords: &[Card]) -> Vec<u8> {
+    let mut cards = build_primary_header(8, &[]).unwrap();
+    cards.extend_from_slice(keywords);
+    hdu_bytes(&cards, &[])
+}
+
+fn commented(keyword: &str, value: Value, comment: &str) -> Card {
+    Card {
+        comment: Some(comment.into()),
+        ..card(keyword, value)
+    }
+}
+
+fn history(text: &str) -> Card {
+    Card {
+        value: None,
+        comment: Some(text.into()),
+        ..card("HISTORY", Value::Integer(0))
+    }
+}
+
+/// A BINTABLE extension. `columns` are (TTYPE, TFORM) pairs and each row is
+/// its raw big-endian bytes.
+fn bintable(extname: &str, columns: &[(&str, &str)], rows: &[Vec<u8>]) -> Vec<u8> {
+    bintable_hdu(extname, columns, &[], rows, &[])
+}
+
+/// As `bintable`, with extra column `keywords` (TSCALn, TNULLn...) and `heap`
+/// appended after the rows for P/Q columns.
+fn bintable_hdu(
+    extname: &str,
+    columns: &[(&str, &str)],
+    keywords: &[Card],
+    rows: &[Vec<u8>],
+    heap: &[u8],
+) -> Vec<u8> {
+    let mut cards = vec![
+        card("XTENSION", Value::String("BINTABLE".into())),
+        card("BITPIX", Value::Integer(8)),
+        card("NAXIS", Value::Integer(2)),
+        card("NAXIS1", Value::Integer(rows[0].len() as i64)),
+        card("NAXIS2", Value::Integer(rows.len() as i64)),
+        card("PCOUNT", Value::Integer(heap.len() as i64)),
+        card("GCOUNT", Value::Integer(1)),
+        card("TFIELDS", Value::Integer(columns.len() as i64)),
+    ];
+    for (i, (name, tform)) in columns.iter().enumerate() {
+        cards.push(card(
+            &format!("TTYPE{}", i + 1),
+            Value::String(name.to_string()),
+        ));
+        cards.push(card(
+            &format!("TFORM{}", i + 1),
+            Value::String(tform.to_string()),
+        ));
+    }
+    cards.extend_from_slice(keywords);
+    cards.push(card("EXTNAME", Value::String(extname.into())));
+    hdu_bytes(&cards, &[rows.concat(), heap.to_vec()].concat())
+}
+
+fn image(extname: &str, naxes: &[usize], pixels: &[f32]) -> Vec<u8> {
+    let mut cards = vec![
+        card("XTENSION", Value::String("IMAGE".into())),
+        card("BITPIX", Value::Integer(-32)),
+        card("NAXIS", Value::Integer(naxes.len() as i64)),
+    ];
+    for (i, n) in naxes.iter().enumerate() {
+        cards.push(card(&format!("NAXIS{}", i + 1), Value::Integer(*n as i64)));
+    }
+    cards.push(card("PCOUNT", Value::Integer(0)));
+    cards.push(card("GCOUNT", Value::Integer(1)));
+    cards.push(card("EXTNAME", Value::String(extname.into())));
+    let data: Vec<u8> = pixels.iter().flat_map(|p| p.to_be_bytes()).collect();
+    hdu_bytes(&cards, &data)
+}
+
+/// Photometry in HDU 1, spectra summaries in HDU 2 and an image in HDU 3.
+fn multi_extension_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("catalogue.fits");
+    let header = primary_with(&[
+        commented("TELESCOP", Value::String("JWST".into()), "Observatory"),
+        commented("EQUINOX", Value::Float(2000.0), "Epoch"),
+        history("Reduced with pipeline v1"),
+        history("Calibrated"),
+    ]);
+    let photometry = bintable_hdu(
+        "PHOTOMETRY",
+        &[("id", "1K"), ("mag", "1D")],
+        &[
+            card("TUNIT2", Value::String("mag".into())),
+            card("TUCD2", Value::String("phot.mag;em.opt.V".into())),
+            card("TCOMM2", Value::String("Apparent \"V\" magnitude".into())),
+            commented("EQUINOX", Value::Float(2015.5), "Gaia epoch"),
+        ],
+        &[
+            [1i64.to_be_bytes(), 18.5f64.to_be_bytes()].concat(),
+            [2i64.to_be_bytes(), 19.25f64.to_be_bytes()].concat(),
+        ],
+        &[],
+    );
+    let spectra = bintable(
+        "SPECTRA",
+        &[("redshift", "1E")],
+        &[
+            0.125f32.to_be_bytes().to_vec(),
+            0.5f32.to_be_bytes().to_vec(),
+            0.75f32.to_be_bytes().to_vec(),
+        ],
+    );
+    let cutout = image("CUTOUT", &[3, 2], &[0., 1., 2., 3., 4., 5.]);
+    fs::write(&path, [header, photometry, spectra, cutout].concat())
+        .expect("failed to write fits file");
+    (dir, path)
+}
+
+/// One column of every binary-table TFORM code that isn't E, D, J, K or L.
+fn all_types_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("types.fits");
+    let columns = [
+        ("bit", "1X"),
+        ("flags", "16X"),
+        ("byte", "1B"),
+        ("short", "1I"),
+        ("cplx", "1C"),
+        ("dcplx", "1M"),
+        ("label", "4A"),
+        ("counts", "1PJ(2)"),
+        ("comment", "1PA(3)"),
+    ];
+    // (count, heap offset) pairs for the P columns.
+    let p = |count: i32, offset: i32| [count.to_be_bytes(), offset.to_be_bytes()].concat();
+    let rows = [
+        [
+            vec![0x80],
+            vec![0x80, 0x01],
+            vec![200],
+            (-5i16).to_be_bytes().to_vec(),
+            [1.5f32.to_be_bytes(), (-2f32).to_be_bytes()].concat(),
+            [3f64.to_be_bytes(), 4f64.to_be_bytes()].concat(),
+            b"abcd".to_vec(),
+            p(2, 0),
+            p(3, 8),
+        ]
+        .concat(),
+        [
+            vec![0x00],
+            vec![0x00, 0x03],
+            vec![7],
+            12i16.to_be_bytes().to_vec(),
+            [0f32.to_be_bytes(), 1f32.to_be_bytes()].concat(),
+            [0f64.to_be_bytes(), 0f64.to_be_bytes()].concat(),
+            b"xy  ".to_vec(),
+            p(0, 0),
+            p(2, 11),
+        ]
+        .concat(),
+    ];
+    let heap = [
+        10i32.to_be_bytes().to_vec(),
+        20i32.to_be_bytes().to_vec(),
+        b"foohi".to_vec(),
+    ]
+    .concat();
+    let table = bintable_hdu("TYPES", &columns, &[], &rows, &heap);
+    fs::write(&path, [primary(), table].concat()).expect("failed to write fits file");
+    (dir, path)
+}
+
+/// Unsigned columns stored with the standard TZERO offsets, a scaled column
+/// with a TNULL sentinel and a float column holding NaN.
+fn scaled_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("scaled.fits");
+    let columns = [
+        ("sbyte", "1B"),
+        ("u16", "1I"),
+        ("u32", "1J"),
+        ("u64", "1K"),
+        ("flux", "1J"),
+        ("mag", "1E"),
+    ];
+    let keywords = [
+        card("TZERO1", Value::Integer(-128)),
+        card("TZERO2", Value::Integer(32768)),
+        card("TZERO3", Value::Integer(2147483648)),
+        card("TZERO4", Value::Float(9223372036854775808.0)),
+        card("TSCAL5", Value::Float(0.5)),
+        card("TZERO5", Value::Integer(10)),
+        card("TNULL5", Value::Integer(-1)),
+    ];
+    let rows = [
+        [
+            vec![0],
+            i16::MIN.to_be_bytes().to_vec(),
+            i32::MIN.to_be_bytes().to_vec(),
+            i64::MIN.to_be_bytes().to_vec(),
+            4i32.to_be_bytes().to_vec(),
+            21.5f32.to_be_bytes().to_vec(),
+        ]
+        .concat(),
+        [
+            vec![255],
+            i16::MAX.to_be_bytes().to_vec(),
+            i32::MAX.to_be_bytes().to_vec(),
+            i64::MAX.to_be_bytes().to_vec(),
+            (-1i32).to_be_bytes().to_vec(),
+            f32::NAN.to_be_bytes().to_vec(),
+        ]
+        .concat(),
+    ];
+    let table = bintable_hdu("SCALED", &columns, &keywords, &rows, &[]);
+    fs::write(&path, [primary(), table].concat()).expect("failed to write fits file");
+    (dir, path)
+}
+
+/// Vector columns: a plain `3E`, a `6I` with TDIM (3,2) and a `2C`.
+fn vector_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("vectors.fits");
+    let columns = [("id", "1J"), ("flux", "3E"), ("cube", "6I"), ("cplx", "2C")];
+    let keywords = [card("TDIM3", Value::String("(3,2)".into()))];
+    let row = |id: i32, offset: i16| {
+        [
+            id.to_be_bytes().to_vec(),
+            [1.5f32, 2.5, 3.5]
+                .iter()
+                .flat_map(|f| (f + offset as f32).to_be_bytes())
+                .collect(),
+            (0..6i16).flat_map(|i| (i + offset).to_be_bytes()).collect(),
+            [1f32, 2., 3., 4.]
+                .iter()
+                .flat_map(|f| f.to_be_bytes())
+                .collect(),
+        ]
+        .concat()
+    };
+    let table = bintable_hdu(
+        "VECTORS",
+        &columns,
+        &keywords,
+        &[row(1, 0), row(2, 10)],
+        &[],
+    );
+    fs::write(&path, [primary(), table].concat()).expect("failed to write fits file");
+    (dir, path)
+}
+
+/// An ASCII TABLE extension with a string, an `I6` column whose TNULL is
+/// "-999", and `F10.4`/`D12.3` columns using blanks, implied decimal points
+/// and exponents without a letter.
+fn ascii_table_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("ascii.fits");
+    let rows = [
+        "vega          42    1.2345   1.500D-03",
+        "sirius b    -999     12345            ",
+        "                  -5.0E-01      2.5-3",
+    ];
+    let columns = [
+        ("name", "A8", 1),
+        ("count", "I6", 11),
+        ("flux", "F10.4", 17),
+        ("err", "D12.3", 27),
+    ];
+    let mut cards = vec![
+        card("XTENSION", Value::String("TABLE".into())),
+        card("BITPIX", Value::Integer(8)),
+        card("NAXIS", Value::Integer(2)),
+        card("NAXIS1", Value::Integer(38)),
+        card("NAXIS2", Value::Integer(rows.len() as i64)),
+        card("PCOUNT", Value::Integer(0)),
+        card("GCOUNT", Value::Integer(1)),
+        card("TFIELDS", Value::Integer(columns.len() as i64)),
+    ];
+    for (i, (name, tform, tbcol)) in columns.iter().enumerate() {
+        let n = i + 1;
+        cards.push(card(&format!("TTYPE{n}"), Value::String((*name).into())));
+        cards.push(card(&format!("TBCOL{n}"), Value::Integer(*tbcol)));
+        cards.push(card(&format!("TFORM{n}"), Value::String((*tform).into())));
+    }
+    cards.push(card("TNULL2", Value::String("-999".into())));
+    cards.push(card("EXTNAME", Value::String("ASCII".into())));
+    let data: Vec<u8> = rows
+        .iter()
+        .flat_map(|row| format!("{row:38}").into_bytes())
+        .collect();
+    fs::write(&path, [primary(), hdu_bytes(&cards, &data)].concat())
+        .expect("failed to write fits file");
+    (dir, path)
+}
+
+/// A file that is only a 3 x 2 primary image of scaled 16-bit pixels, one of
+/// them BLANK.
+fn image_only_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("image.fits");
+    let cards = [
+        card("SIMPLE", Value::Logical(true)),
+        card("BITPIX", Value::Integer(16)),
+        card("NAXIS", Value::Integer(2)),
+        card("NAXIS1", Value::Integer(3)),
+        card("NAXIS2", Value::Integer(2)),
+        card("BSCALE", Value::Float(0.5)),
+        card("BZERO", Value::Integer(10)),
+        card("BLANK", Value::Integer(-1)),
+        card("BUNIT", Value::String("adu".into())),
+    ];
+    let pixels: Vec<u8> = [0i16, 2, 4, -1, 8, 10]
+        .iter()
+        .flat_map(|p| p.to_be_bytes())
+        .collect();
+    fs::write(&path, hdu_bytes(&cards, &pixels)).expect("failed to write fits file");
+    (dir, path)
+}
+
+fn stdout_of(args: &[&str], fits: &PathBuf) -> String {
+    let output = dog(args, fits).success().get_output().stdout.clone();
+    String::from_utf8(output).expect("stdout was not utf8")
+}
+
+fn dog(args: &[&str], fits: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(fits)
+        .assert()
+}
+
+#[test]
+fn default_reads_first_table() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["-d"], &fits)
+        .success()
+        .stdout(predicate::str::contains("1 18.5"))
+        .stdout(predicate::str::contains("2 19.25"));
+}
+
+#[test]
+fn hdu_selected_by_index() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["--hdu", "2", "-n"], &fits)
+        .success()
+        .stdout(predicate::str::contains("redshift"))
+        .stdout(predicate::str::contains("mag").not());
+}
+
+#[test]
+fn hdu_selected_by_extname() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["--hdu", "spectra", "-d"], &fits)
+        .success()
+        .stdout(predicate::str::contains("0.125"))
+        .stdout(predicate::str::contains("0.75"));
+}
+
+#[test]
+fn head_and_tail_print_only_their_rows() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["--hdu", "spectra", "-c", "redshift", "-H", "1"], &fits)
+        .success()
+        .stdout(predicate::str::contains("0.125"))
+        .stdout(predicate::str::contains("0.75").not());
+    dog(&["--hdu", "spectra", "-c", "redshift", "-t", "1"], &fits)
+        .success()
+        .stdout(predicate::str::contains("0.75"))
+        .stdout(predicate::str::contains("0.125").not());
+}
+
+#[test]
+fn missing_hdu_fails() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["--hdu", "9"], &fits)
+        .failure()
+        .stderr(predicate::str::contains("does not exist"));
+    dog(&["--hdu", "NOPE"], &fits)
+        .failure()
+        .stderr(predicate::str::contains("NOPE"));
+}
+
+#[test]
+fn image_hdu_reads_as_pixel_table() {
+    let (_dir, fits) = multi_extension_fits();
+    let data = stdout_of(&["--hdu", "3", "-d"], &fits);
+    assert_eq!(
+        data.lines().collect::<Vec<_>>(),
+        ["1 1 0.0", "2 1 1.0", "3 1 2.0", "1 2 3.0", "2 2 4.0", "3 2 5.0"]
+    );
+    dog(&["--hdu", "0"], &fits)
+        .failure()
+        .stderr(predicate::str::contains("HDU 0 has no pixels"));
+}
+
+#[test]
+fn list_hdus() {
+    let (_dir, fits) = multi_extension_fits();
+    let output = dog(&["--list-hdus"], &fits)
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let listed = String::from_utf8(output).expect("stdout was not utf8");
+    let lines: Vec<&str> = listed.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].contains("PRIMARY") && lines[0].contains("primary image"));
+    assert!(lines[1].contains("PHOTOMETRY") && lines[1].contains("2 rows x 2 columns"));
+    assert!(lines[2].contains("SPECTRA") && lines[2].contains("3 rows x 1 columns"));
+    assert!(lines[3].contains("CUTOUT") && lines[3].contains("3 x 2"));
+}
+
+#[test]
+fn trailing_padding_and_junk_end_the_hdu_list() {
+    let (_dir, fits) = multi_extension_fits();
+    let mut bytes = fs::read(&fits).unwrap();
+    bytes.extend(vec![0u8; 2 * 2880]);
+    bytes.extend(b"not a header".repeat(240));
+    fs::write(&fits, bytes).unwrap();
+    let listed = stdout_of(&["--list-hdus"], &fits);
+    assert_eq!(listed.lines().count(), 4, "{listed}");
+    dog(&["--hdu", "spectra", "-d"], &fits)
+        .success()
+        .stdout(predicate::str::contains("0.75"));
+}
+
+#[test]
+fn keyword_from_primary_header() {
+    let (_dir, fits) = multi_extension_fits();
+    assert_eq!(
+        stdout_of(&["-k", "telescop"], &fits),
+        "JWST / Observatory\n"
+    );
+}
+
+#[test]
+fn extension_keyword_overrides_primary() {
+    let (_dir, fits) = multi_extension_fits();
+    assert_eq!(
+        stdout_of(&["-k", "EQUINOX"], &fits),
+        "2015.5 / Gaia epoch\n"
+    );
+    assert_eq!(
+        stdout_of(&["-k", "EQUINOX", "--hdu", "0"], &fits),
+        "2000 / Epoch\n"
+    );
+    assert_eq!(
+        stdout_of(&["-k", "EXTNAME", "--hdu", "SPECTRA"], &fits),
+        "SPECTRA\n"
+    );
+}
+
+#[test]
+fn every_history_card_is_printed() {
+    let (_dir, fits) = multi_extension_fits();
+    assert_eq!(
+        stdout_of(&["-k", "HISTORY"], &fits),
+        "Reduced with pipeline v1\nCalibrated\n"
+    );
+}
+
+#[test]
+fn missing_keyword_fails() {
+    let (_dir, fits) = multi_extension_fits();
+    dog(&["-k", "OBSERVER"], &fits)
+        .failure()
+        .stderr(predicate::str::contains("not in the FITS header"));
+}
+
+#[test]
+fn list_keywords_shows_primary_and_extension() {
+    let (_dir, fits) = multi_extension_fits();
+    let listed = stdout_of(&["--list-keywords"], &fits);
+    let lines: Vec<&str> = listed.lines().collect();
+    assert_eq!(lines[0], "HDU 0 PRIMARY");
+    assert!(lines.contains(&"TELESCOP = JWST / Observatory"), "{listed}");
+    assert!(lines.contains(&"HISTORY  Calibrated"), "{listed}");
+    assert!(lines.contains(&"HDU 1 PHOTOMETRY"), "{listed}");
+    assert!(lines.contains(&"TFORM2   = 1D"), "{listed}");
+    assert!(!listed.contains("SPECTRA"), "{listed}");
+}
+
+#[test]
+fn hdu_flag_rejected_for_parquet() {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .args(["--hdu", "1", "tests/fixtures/test.parquet"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only applies to FITS"));
+}
+
+#[test]
+fn every_tform_code_is_read() {
+    let (_dir, fits) = all_types_fits();
+    let schema = stdout_of(&["--schema"], &fits);
+    for dtype in [
+        "Boolean",
+        "UInt16",
+        "UInt8",
+        "Int16",
+        "Struct",
+        "String",
+        "List(Int32)",
+    ] {
+        assert!(schema.contains(dtype), "{dtype} missing from {schema}");
+    }
+}
+
+#[test]
+fn scalar_tform_values() {
+    let (_dir, fits) = all_types_fits();
+    let data = stdout_of(&["-d", "-c", "bit,flags,byte,short,label,comment"], &fits);
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(lines, ["true 32769 200 -5 abcd foo", "false 3 7 12 xy hi"]);
+}
+
+#[test]
+fn complex_and_variable_length_values() {
+    let (_dir, fits) = all_types_fits();
+    let peak = stdout_of(&["-p", "-c", "cplx,counts"], &fits);
+    assert!(peak.contains("{1.5,-2.0}"), "{peak}");
+    assert!(peak.contains("[10, 20]"), "{peak}");
+    assert!(peak.contains("[]"), "{peak}");
+}
+
+#[test]
+fn tzero_offsets_give_unsigned_columns() {
+    let (_dir, fits) = scaled_fits();
+    let schema = stdout_of(&["--schema"], &fits);
+    for (column, dtype) in [
+        ("sbyte", "Int8"),
+        ("u16", "UInt16"),
+        ("u32", "UInt32"),
+        ("u64", "UInt64"),
+        ("flux", "Float64"),
+    ] {
+        assert!(
+            schema.contains(&format!("\"{column}\": {dtype}")),
+            "{column} is not {dtype} in {schema}"
+        );
+    }
+    let data = stdout_of(&["-d", "-c", "sbyte,u16,u32,u64"], &fits);
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(
+        lines,
+        ["-128 0 0 0", "127 65535 4294967295 18446744073709551615"]
+    );
+}
+
+#[test]
+fn tnull_and_nan_are_null() {
+    let (_dir, fits) = scaled_fits();
+    let data = stdout_of(&["-d", "-c", "flux,mag"], &fits);
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(lines, ["12.0 21.5", " "]);
+    dog(&["--stats", "-c", "flux,mag"], &fits)
+        .success()
+        .stdout(predicate::str::contains("null counts: 1"))
+        .stdout(predicate::str::contains("max: 12.0"));
+}
+
+#[test]
+fn variable_length_elements_are_scaled_and_nulled() {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("heap.fits");
+    let keywords = [
+        card("TZERO1", Value::Integer(2147483648)),
+        card("TNULL2", Value::Integer(-1)),
+    ];
+    let p = |count: i32, offset: i32| [count.to_be_bytes(), offset.to_be_bytes()].concat();
+    let heap = [
+        i32::MIN.to_be_bytes().to_vec(),
+        i32::MAX.to_be_bytes().to_vec(),
+        5i16.to_be_bytes().to_vec(),
+        (-1i16).to_be_bytes().to_vec(),
+    ]
+    .concat();
+    let table = bintable_hdu(
+        "HEAP",
+        &[("counts", "1PJ(2)"), ("levels", "1PI(2)")],
+        &keywords,
+        &[[p(2, 0), p(2, 8)].concat()],
+        &heap,
+    );
+    fs::write(&path, [primary(), table].concat()).expect("failed to write fits file");
+
+    let schema = stdout_of(&["--schema"], &path);
+    assert!(schema.contains("\"counts\": List(UInt32)"), "{schema}");
+    assert!(schema.contains("\"levels\": List(Int16)"), "{schema}");
+    let peak = stdout_of(&["-p"], &path);
+    assert!(peak.contains("[0, 4294967295]"), "{peak}");
+    assert!(peak.contains("[5, null]"), "{peak}");
+}
+
+#[test]
+fn vector_columns_stay_whole() {
+    let (_dir, fits) = vector_fits();
+    let schema = stdout_of(&["--schema"], &fits);
+    assert!(schema.contains("\"flux\": Array(Float32, 3)"), "{schema}");
+    assert!(
+        schema.contains("\"cube\": Array(Array(Int16, 3), 2)"),
+        "{schema}"
+    );
+    let data = stdout_of(&["-d"], &fits);
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "1 [1.5,2.5,3.5] [[0,1,2],[3,4,5]] [{1.0,2.0},{3.0,4.0}]",
+            "2 [11.5,12.5,13.5] [[10,11,12],[13,14,15]] [{1.0,2.0},{3.0,4.0}]"
+        ]
+    );
+}
+
+#[test]
+fn explode_vectors_flag() {
+    let (_dir, fits) = vector_fits();
+    let names = stdout_of(&["--explode-vectors", "-n"], &fits);
+    let names: Vec<&str> = names.lines().collect();
+    assert_eq!(names.len(), 12);
+    assert_eq!(&names[..4], ["id", "flux_0", "flux_1", "flux_2"]);
+}
+
+#[test]
+fn vector_column_stats_cover_every_element() {
+    let (_dir, fits) = vector_fits();
+    dog(&["--stats", "-c", "cube"], &fits)
+        .success()
+        .stdout(predicate::str::contains("min: 0"))
+        .stdout(predicate::str::contains("max: 15"));
+}
+
+#[test]
+fn vector_columns_convert_to_parquet() {
+    let (dir, fits) = vector_fits();
+    dog(&["--convert"], &fits).success();
+    let parquet = dir.path().join("vectors_converted.parquet");
+    let schema = stdout_of(&["--schema"], &parquet);
+    assert!(
+        schema.contains("\"cube\": Array(Array(Int16, 3), 2)"),
+        "{schema}"
+    );
+    assert_eq!(stdout_of(&["-d"], &parquet), stdout_of(&["-d"], &fits));
+}
+
+#[test]
+fn convert_keeps_header_cards() {
+    let (dir, fits) = multi_extension_fits();
+    dog(&["--convert"], &fits).success();
+    let parquet = dir.path().join("catalogue_converted.parquet");
+    let header = stdout_of(&["-k", "fits_header"], &parquet);
+    let cards: Vec<&str> = header.lines().collect();
+    assert!(
+        cards.iter().any(|c| c.starts_with("TELESCOP= 'JWST")),
+        "{header}"
+    );
+    assert!(cards.contains(&"HISTORY Calibrated"), "{header}");
+    assert!(cards.contains(&"EXTNAME = 'PHOTOMETRY'"), "{header}");
+    assert!(
+        !header.contains("TFORM1") && !header.contains("NAXIS"),
+        "{header}"
+    );
+    assert_eq!(stdout_of(&["-d"], &parquet), "1 18.5\n2 19.25\n");
+}
+
+#[test]
+fn convert_keeps_column_descriptions_as_field_metadata() {
+    let (dir, fits) = multi_extension_fits();
+    dog(&["--convert"], &fits).success();
+    let parquet = fs::File::open(dir.path().join("catalogue_converted.parquet")).unwrap();
+    let schema = ParquetReader::new(parquet).schema().unwrap();
+    let mag = schema.get("mag").unwrap().metadata.clone().unwrap();
+    assert_eq!(mag.get("unit").map(|v| v.as_str()), Some("mag"));
+    assert_eq!(
+        mag.get("ucd").map(|v| v.as_str()),
+        Some("phot.mag;em.opt.V")
+    );
+    assert_eq!(
+        mag.get("description").map(|v| v.as_str()),
+        Some("Apparent \"V\" magnitude")
+    );
+    assert!(schema.get("id").unwrap().metadata.is_none());
+}
+
+#[test]
+fn convert_generates_maml() {
+    let (dir, fits) = multi_extension_fits();
+    dog(&["--convert", "--maml"], &fits).success();
+    let parquet = dir.path().join("catalogue_converted.parquet");
+    let maml = stdout_of(&["-k", "maml"], &parquet);
+    let lines: Vec<&str> = maml.lines().collect();
+    for expected in [
+        "dataset: catalogue",
+        "table: PHOTOMETRY",
+        "- name: id",
+        "  data_type: int64",
+        "- name: mag",
+        "  unit: mag",
+        "  ucd: phot.mag;em.opt.V",
+        "  info: \"Apparent \\\"V\\\" magnitude\"",
+        "  data_type: float64",
+    ] {
+        assert!(lines.contains(&expected), "{expected} missing from\n{maml}");
+    }
+}
+
+#[test]
+fn maml_quotes_what_yaml_would_misread() {
+    let dir = tempdir().expect("create temp dir");
+    let fits = dir.path().join("odd.fits");
+    let table = bintable_hdu(
+        "&PHOT",
+        &[("#id", "1K"), ("*pos", "1D")],
+        &[card("TUNIT2", Value::String("deg: J2000".into()))],
+        &[[1i64.to_be_bytes(), 2.5f64.to_be_bytes()].concat()],
+        &[],
+    );
+    fs::write(&fits, [primary(), table].concat()).expect("failed to write fits file");
+    dog(&["--convert", "--maml"], &fits).success();
+    let parquet = dir.path().join("odd_converted.parquet");
+
+    let maml = stdout_of(&["-k", "maml"], &parquet);
+    let docs = YamlLoader::load_from_str(&maml).expect("maml is not YAML");
+    let doc = &docs[0];
+    assert_eq!(doc["table"].as_str(), Some("&PHOT"), "{maml}");
+    assert_eq!(doc["fields"][0]["name"].as_str(), Some("#id"), "{maml}");
+    assert_eq!(doc["fields"][1]["name"].as_str(), Some("*pos"), "{maml}");
+    assert_eq!(doc["fields"][1]["unit"].as_str(), Some("deg: J2000"), "{maml}");
+
+    // A subset keeps the quoted entry for the column it selects.
+    let subset = dir.path().join("subset.parquet");
+    dog(&["-c", "*pos", "-o", subset.to_str().unwrap()], &parquet).success();
+    let trimmed = stdout_of(&["-k", "maml"], &subset);
+    let docs = YamlLoader::load_from_str(&trimmed).expect("trimmed maml is not YAML");
+    let fields = docs[0]["fields"].as_vec().expect("fields is not a list");
+    assert_eq!(fields.len(), 1, "{trimmed}");
+    assert_eq!(fields[0]["name"].as_str(), Some("*pos"), "{trimmed}");
+}
+
+/// Ten rows of `id` with a variable-length column whose last descriptor points
+/// past the end of the heap, so only reads that skip that cell succeed.
+fn damaged_heap_fits() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("damaged.fits");
+    let rows: Vec<Vec<u8>> = (0..10i32)
+        .map(|id| {
+            let (count, offset) = if id == 9 { (4i32, 1000i32) } else { (1, 0) };
+            [id.to_be_bytes(), count.to_be_bytes(), offset.to_be_bytes()].concat()
+        })
+        .collect();
+    let heap = 7i32.to_be_bytes();
+    let table = bintable_hdu(
+        "DAMAGED",
+        &[("id", "1J"), ("counts", "1PJ(4)")],
+        &[],
+        &rows,
+        &heap,
+    );
+    fs::write(&path, [primary(), table].concat()).expect("failed to write fits file");
+    (dir, path)
+}
+
+#[test]
+fn scan_only_decodes_selected_columns() {
+    let (_dir, fits) = damaged_heap_fits();
+    dog(&["-d"], &fits)
+        .failure()
+        .stderr(predicate::str::contains("points past the end of the heap"));
+    let ids = stdout_of(&["-d", "-c", "id"], &fits);
+    assert_eq!(ids.lines().count(), 10);
+}
+
+#[test]
+fn tail_reads_the_last_rows() {
+    let (_dir, fits) = damaged_heap_fits();
+    let tail = stdout_of(&["-t", "3", "-c", "id"], &fits);
+    let lines: Vec<&str> = tail.lines().take(3).collect();
+    assert_eq!(lines, ["7", "8", "9"]);
+}
+
+#[test]
+fn checksums_are_not_carried_into_outputs() {
+    let dir = tempdir().expect("create temp dir");
+    let fits = dir.path().join("summed.fits");
+    let checksums = |datasum: &str| {
+        [
+            card("CHECKSUM", Value::String("9bDaA9ZZ9bCaA9ZZ".into())),
+            card("DATASUM", Value::String(datasum.into())),
+        ]
+    };
+    let header = primary_with(&checksums("0"));
+    let table = bintable_hdu(
+        "STARS",
+        &[("id", "1K")],
+        &checksums("2831109"),
+        &[1i64.to_be_bytes().to_vec(), 2i64.to_be_bytes().to_vec()],
+        &[],
+    );
+    fs::write(&fits, [header, table].concat()).unwrap();
+
+    dog(&["--convert"], &fits).success();
+    let parquet = dir.path().join("summed_converted.parquet");
+    let header = stdout_of(&["-k", "fits_header"], &parquet);
+    assert!(header.contains("EXTNAME") && !header.contains("SUM"), "{header}");
+
+    let subset = dir.path().join("subset.fits");
+    dog(&["-f", "id > 1", "-o", subset.to_str().unwrap()], &fits).success();
+    let cards = stdout_of(&["--list-keywords"], &subset);
+    assert!(cards.contains("EXTNAME") && !cards.contains("SUM"), "{cards}");
+}
+
+#[test]
+fn fits_header_survives_a_round_trip_through_parquet() {
+    let (dir, fits) = multi_extension_fits();
+    dog(&["--convert", "--maml"], &fits).success();
+    let parquet = dir.path().join("catalogue_converted.parquet");
+    dog(
+        &["--convert", "--to", "fits", "--header-keywords"],
+        &parquet,
+    )
+    .success();
+    let back = dir.path().join("catalogue_converted_converted.fits");
+    assert_eq!(stdout_of(&["-d"], &back), stdout_of(&["-d"], &fits));
+    assert_eq!(
+        stdout_of(&["-k", "TELESCOP"], &back),
+        stdout_of(&["-k", "TELESCOP"], &fits)
+    );
+    let cards = stdout_of(&["--list-keywords"], &back);
+    let cards: Vec<&str> = cards.lines().collect();
+    for expected in [
+        "TUNIT2   = mag",
+        "TUCD2    = phot.mag;em.opt.V",
+        "EQUINOX  = 2015.5 / Gaia epoch",
+        "EXTNAME  = PHOTOMETRY",
+        "HISTORY  Calibrated",
+        "COMMENT  maml:",
+        "COMMENT  table: PHOTOMETRY",
+    ] {
+        assert!(
+            cards.contains(&expected),
+            "{expected} missing from {cards:?}"
+        );
+    }
+    assert!(!cards.iter().any(|c| c.starts_with("EQUINOX  = 2000")));
+}
+
+#[test]
+fn ascii_table_is_read() {
+    let (_dir, fits) = ascii_table_fits();
+    let schema = stdout_of(&["--schema"], &fits);
+    for (column, dtype) in [
+        ("name", "String"),
+        ("count", "Int64"),
+        ("flux", "Float64"),
+        ("err", "Float64"),
+    ] {
+        assert!(
+            schema.contains(&format!("\"{column}\": {dtype}")),
+            "{column} is not {dtype} in {schema}"
+        );
+    }
+    let data = stdout_of(&["-d"], &fits);
+    assert_eq!(
+        data.lines().collect::<Vec<_>>(),
+        [
+            "vega 42 1.2345 0.0015",
+            "\"sirius b\"  1.2345 ",
+            "\"\"  -0.5 0.0025"
+        ]
+    );
+}
+
+#[test]
+fn ascii_table_columns_are_projected() {
+    let (_dir, fits) = ascii_table_fits();
+    dog(&["--stats", "-c", "count,err"], &fits)
+        .success()
+        .stdout(predicate::str::contains("null counts: 2"));
+}
+
+#[test]
+fn image_only_file_reads_scaled_pixels() {
+    let (_dir, fits) = image_only_fits();
+    let schema = stdout_of(&["--schema"], &fits);
+    assert!(schema.contains("\"value\": Float64"), "{schema}");
+    let data = stdout_of(&["-d", "-c", "value"], &fits);
+    assert_eq!(data, "10.0\n11.0\n12.0\n\n14.0\n15.0\n");
+}
+
+#[test]
+fn image_summary_streams_pixel_stats() {
+    let (_dir, fits) = image_only_fits();
+    for flag in ["--summary", "--stats"] {
+        let summary = stdout_of(&[flag], &fits);
+        for line in [
+            "Shape: 3 x 2",
+            "BITPIX: 16",
+            "BSCALE: 0.5",
+            "BZERO: 10",
+            "BUNIT: adu",
+            "Pixels: 6",
+            "min: 10",
+            "mean: 12.4",
+            "max: 15",
+            "null counts: 1",
+        ] {
+            assert!(summary.contains(line), "{line} missing from {summary}");
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use assert_cmd::Command;
+use polars::prelude::*;
+use predicates::prelude::*;
+use std::{fs::File, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+/// A parquet table with a column for each kind of FITS TFORM the writer uses,
+/// with nulls in most of them.
+fn typed_parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("typed.parquet");
+    let mut df = df![
+        "id" => [1i64, 2, 3],
+        "flag" => [Some(true), None, Some(false)],
+        "small" => [Some(-3i8), None, Some(7)],
+        "count" => [Some(0u16), Some(65534), None],
+        "big" => [Some(u64::MAX - 1), Some(0), None],
+        "flux" => [Some(1.5f32), None, Some(-2.0)],
+        "name" => [Some("vega"), None, Some("sirius b")],
+        "mags" => [
+            Series::new("".into(), [1.0f64, 2.0]),
+            Series::new("".into(), [3.0f64, 4.0]),
+            Series::new("".into(), [5.0f64, 6.0]),
+        ],
+        "hits" => [
+            Series::new("".into(), [1i32, 2, 3]),
+            Series::new("".into(), Vec::<i32>::new()),
+            Series::new("".into(), [4i32]),
+        ],
+    ]
+    .unwrap();
+    let cube = Series::new("cube".into(), (0..18i16).collect::<Vec<_>>())
+        .reshape_array(&[
+            ReshapeDimension::new(3),
+            ReshapeDimension::new(2),
+            ReshapeDimension::new(3),
+        ])
+        .unwrap();
+    df.with_column(cube.into()).unwrap();
+    ParquetWriter::new(File::create(&path).unwrap())
+        .finish(&mut df)
+        .unwrap();
+    (dir, path)
+}
+
+fn stdout_of(args: &[&str], file: &PathBuf) -> String {
+    let output = dog(args, file).success().get_output().stdout.clone();
+    String::from_utf8(output).expect("stdout was not utf8")
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn convert_to_fits_round_trips_dtypes() {
+    let (dir, parquet) = typed_parquet();
+    dog(&["--convert", "--to", "fits"], &parquet).success();
+    let fits = dir.path().join("typed_converted.fits");
+    let schema = stdout_of(&["--schema"], &fits);
+    for (column, dtype) in [
+        ("id", "Int64"),
+        ("flag", "Boolean"),
+        ("small", "Int8"),
+        ("count", "UInt16"),
+        ("big", "UInt64"),
+        ("flux", "Float32"),
+        ("name", "String"),
+        ("mags", "Array(Float64, 2)"),
+        ("hits", "List(Int32)"),
+        ("cube", "Array(Array(Int16, 3), 2)"),
+    ] {
+        assert!(
+            schema.contains(&format!("\"{column}\": {dtype}")),
+            "{column} is not {dtype} in {schema}"
+        );
+    }
+    let data = stdout_of(&["-d", "-c", "id,small,count,big,flux,name,hits"], &fits);
+    let lines: Vec<&str> = data.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "1 -3 0 18446744073709551614 1.5 vega [1,2,3]",
+            "2  65534 0  \"\" []",
+            "3 7   -2.0 \"sirius b\" [4]",
+        ]
+    );
+}
+
+#[test]
+fn nulls_are_marked_with_tnull() {
+    let (dir, parquet) = typed_parquet();
+    dog(&["--convert", "--to", "fits"], &parquet).success();
+    let fits = dir.path().join("typed_converted.fits");
+    let cards = stdout_of(&["--list-keywords"], &fits);
+    assert!(cards.contains("TNULL3   = 0"), "{cards}");
+    assert!(cards.contains("TZERO3   = -128"), "{cards}");
+    assert!(cards.contains("TNULL4   = 32767"), "{cards}");
+    assert!(cards.contains("TDIM10   = (3,2)"), "{cards}");
+    assert!(!cards.contains("TNULL1 "), "{cards}");
+    // 2^63 is written exactly, not as a rounded float.
+    let header = String::from_utf8_lossy(&std::fs::read(&fits).unwrap()[2880..5760]).into_owned();
+    assert!(header.contains(&format!("TZERO5  = {:>20}", "9223372036854775808")), "{header}");
+    dog(&["--stats", "-c", "flux"], &fits)
+        .success()
+        .stdout(predicate::str::contains("null counts: 1"));
+}
+
+#[test]
+fn outfile_with_fits_extension_writes_fits() {
+    let (dir, parquet) = typed_parquet();
+    let out = dir.path().join("selection.fits");
+    dog(&["-c", "id,name", "-o", out.to_str().unwrap()], &parquet).success();
+    let data = stdout_of(&["-d"], &out);
+    assert_eq!(data, "1 vega\n2 \"\"\n3 \"sirius b\"\n");
+}
+
+#[test]
+fn convert_to_fits_rejects_fits_input() {
+    let (dir, parquet) = typed_parquet();
+    dog(&["--convert", "--to", "fits"], &parquet).success();
+    dog(
+        &["--convert", "--to", "fits"],
+        &dir.path().join("typed_converted.fits"),
+    )
+    .failure()
+    .stderr(predicate::str::contains("already a FITS file"));
+}
+
+#[test]
+fn tables_too_wide_for_fits_are_refused() {
+    let dir = tempdir().expect("create temp dir");
+    let csv = dir.path().join("wide.csv");
+    let names: Vec<String> = (1..=1000).map(|i| format!("c{i}")).collect();
+    let values = vec!["1"; 1000];
+    std::fs::write(&csv, format!("{}\n{}\n", names.join(","), values.join(","))).unwrap();
+    dog(&["--convert", "--to", "fits"], &csv)
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("at most 999 columns"));
+    dog(&["-c", "c1,c999", "-o", dir.path().join("narrow.fits").to_str().unwrap()], &csv).success();
+}
+
+#[test]
+fn column_names_too_long_for_ttype_are_refused() {
+    let dir = tempdir().expect("create temp dir");
+    let csv = dir.path().join("long.csv");
+    let name = "n".repeat(100);
+    std::fs::write(&csv, format!("id,{name}\n1,2\n")).unwrap();
+    dog(&["--convert", "--to", "fits"], &csv)
+        .failure()
+        .stderr(predicate::str::contains("too long for a FITS TTYPE card"));
+    assert!(!dir.path().join("long_converted.fits").exists());
+}
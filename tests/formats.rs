@@ -0,0 +1,213 @@
+use assert_cmd::Command;
+use polars::prelude::*;
+use predicates::prelude::*;
+use std::{
+    fs::{self, File},
+    path::PathBuf,
+};
+use tempfile::{tempdir, TempDir};
+
+const CSV: &str = "id,redshift\nxyz,0.1\nabc,0.2\n";
+
+/// The parquet fixture copied to `name`, which may have any extension or none.
+fn parquet_named(name: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    fs::copy("tests/fixtures/test.parquet", &path).expect("failed to copy parquet to temp dir");
+    (dir, path)
+}
+
+fn csv_named(name: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    fs::write(&path, CSV).expect("failed to write csv");
+    (dir, path)
+}
+
+fn fixture_frame() -> DataFrame {
+    df!["id" => ["xyz", "abc"], "redshift" => [0.1, 0.2]].unwrap()
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn reads_fixture(file: &PathBuf) {
+    dog(&["-d"], file)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn parquet_is_recognised_by_its_magic_bytes() {
+    for name in ["catalogue.parq", "catalogue.PARQUET", "pipeline_output"] {
+        let (_dir, file) = parquet_named(name);
+        reads_fixture(&file);
+    }
+}
+
+#[test]
+fn csv_extensions_are_case_insensitive() {
+    for name in ["foo.CSV", "pipeline_output"] {
+        let (_dir, file) = csv_named(name);
+        reads_fixture(&file);
+    }
+}
+
+#[test]
+fn fits_extensions_and_magic_bytes() {
+    let (dir, parquet) = parquet_named("test.parquet");
+    dog(&["--convert", "--to", "fits"], &parquet).success();
+    let fits = dir.path().join("test_converted.fits");
+    for name in ["data.fit", "table.fts", "no_extension"] {
+        let renamed = dir.path().join(name);
+        fs::copy(&fits, &renamed).expect("failed to copy fits file");
+        reads_fixture(&renamed);
+        dog(&["--list-hdus"], &renamed).success();
+    }
+}
+
+#[test]
+fn format_overrides_the_extension() {
+    let (_dir, file) = csv_named("mislabelled.parquet");
+    dog(&["-d"], &file).failure();
+    dog(&["--format", "csv", "-d"], &file)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn convert_names_output_after_the_stem() {
+    let (dir, file) = csv_named("foo.CSV");
+    dog(&["--convert"], &file).success();
+    reads_fixture(&dir.path().join("foo_converted.parquet"));
+}
+
+#[test]
+fn truncated_gzip_input_is_reported() {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("table.csv");
+    fs::write(&path, [0x1f, 0x8b, 0x08, 0x00]).expect("failed to write file");
+    dog(&["-d"], &path)
+        .failure()
+        .stderr(predicate::str::contains("could not be decompressed as Gzip"));
+}
+
+#[test]
+fn ipc_file_and_stream_are_read() {
+    let dir = tempdir().expect("create temp dir");
+    let file = dir.path().join("sim.feather");
+    IpcWriter::new(File::create(&file).unwrap())
+        .finish(&mut fixture_frame())
+        .unwrap();
+    reads_fixture(&file);
+
+    let stream = dir.path().join("sim_stage_output");
+    IpcStreamWriter::new(File::create(&stream).unwrap())
+        .finish(&mut fixture_frame())
+        .unwrap();
+    reads_fixture(&stream);
+}
+
+#[test]
+fn outfile_with_arrow_extension_writes_ipc() {
+    let (dir, parquet) = parquet_named("test.parquet");
+    let out = dir.path().join("selection.arrow");
+    dog(
+        &["-c", "id,redshift", "-o", out.to_str().unwrap()],
+        &parquet,
+    )
+    .success();
+    assert!(fs::read(&out).unwrap().starts_with(b"ARROW1"));
+    reads_fixture(&out);
+}
+
+#[test]
+fn convert_to_and_from_ipc() {
+    let (dir, parquet) = parquet_named("test.parquet");
+    dog(&["--convert", "--to", "ipc"], &parquet).success();
+    let ipc = dir.path().join("test_converted.arrow");
+    reads_fixture(&ipc);
+    dog(&["--convert"], &ipc).success();
+    reads_fixture(&dir.path().join("test_converted_converted.parquet"));
+    dog(&["--convert", "--to", "ipc"], &ipc)
+        .failure()
+        .stderr(predicate::str::contains("already an Arrow IPC file"));
+}
+
+#[test]
+fn json_array_and_ndjson_are_read() {
+    let dir = tempdir().expect("create temp dir");
+    let array = dir.path().join("service.json");
+    fs::write(
+        &array,
+        "[\n{\"id\": \"xyz\", \"redshift\": 0.1},\n{\"id\": \"abc\", \"redshift\": 0.2}\n]\n",
+    )
+    .unwrap();
+    reads_fixture(&array);
+
+    let lines = dir.path().join("service_output");
+    fs::write(
+        &lines,
+        "{\"id\": \"xyz\", \"redshift\": 0.1}\n{\"id\": \"abc\", \"redshift\": 0.2}\n",
+    )
+    .unwrap();
+    reads_fixture(&lines);
+    dog(&["-f", "redshift > 0.15", "-d"], &lines)
+        .success()
+        .stdout(predicate::eq("abc 0.2\n"));
+}
+
+#[test]
+fn nested_json_objects_become_structs() {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("nested.ndjson");
+    fs::write(
+        &path,
+        "{\"id\": 1, \"pos\": {\"ra\": 10.5, \"dec\": -3.0}}\n{\"id\": 2, \"pos\": {\"ra\": 11.0, \"dec\": 4.5}}\n",
+    )
+    .unwrap();
+    dog(&["--schema"], &path)
+        .success()
+        .stdout(predicate::str::contains("Struct"));
+    dog(&["--json", "-c", "pos", "-t", "1"], &path)
+        .success()
+        .stdout(predicate::str::starts_with(
+            "{\"pos\":{\"ra\":11.0,\"dec\":4.5}}\n",
+        ));
+}
+
+#[test]
+fn json_outputs() {
+    let (dir, parquet) = parquet_named("test.parquet");
+    let array = dir.path().join("selection.json");
+    dog(
+        &["-c", "id,redshift", "-o", array.to_str().unwrap()],
+        &parquet,
+    )
+    .success();
+    assert!(fs::read_to_string(&array).unwrap().starts_with('['));
+    reads_fixture(&array);
+
+    let lines = dir.path().join("selection.ndjson");
+    dog(
+        &["-c", "id,redshift", "-o", lines.to_str().unwrap()],
+        &parquet,
+    )
+    .success();
+    reads_fixture(&lines);
+
+    dog(&["--convert", "--to", "ndjson"], &parquet).success();
+    reads_fixture(&dir.path().join("test_converted.ndjson"));
+
+    dog(&["--json", "-d"], &parquet)
+        .success()
+        .stdout(predicate::eq(
+            "{\"id\":\"xyz\",\"redshift\":0.1}\n{\"id\":\"abc\",\"redshift\":0.2}\n",
+        ));
+}
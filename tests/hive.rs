@@ -0,0 +1,59 @@
+use assert_cmd::Command;
+use polars::prelude::*;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+// release/field=G09/part-0.parquet and release/field=G12/part-0.parquet
+fn dataset() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let release = dir.path().join("release");
+    for (field, ids, redshifts) in [
+        ("G09", vec![1i64, 2], vec![0.05, 0.2]),
+        ("G12", vec![3i64], vec![0.08]),
+    ] {
+        let partition = release.join(format!("field={field}"));
+        fs::create_dir_all(&partition).unwrap();
+        let mut df = df!("id" => ids, "z" => redshifts).unwrap();
+        let file = fs::File::create(partition.join("part-0.parquet")).unwrap();
+        ParquetWriter::new(file).finish(&mut df).unwrap();
+    }
+    (dir, release)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn directory_is_read_as_one_table() {
+    let (_dir, release) = dataset();
+    dog(&["-c", "id,field", "-d"], &release)
+        .success()
+        .stdout(predicate::eq("1 G09\n2 G09\n3 G12\n"));
+}
+
+#[test]
+fn partition_columns_can_be_filtered_on() {
+    let (dir, release) = dataset();
+    dog(&["-c", "id,z,field", "-f", "field = 'G09' and z < 0.1", "-d"], &release)
+        .success()
+        .stdout(predicate::eq("1 0.05 G09\n"));
+
+    let out = dir.path().join("g12.csv");
+    dog(&["-c", "id,z,field", "-f", "field = 'G12'", "-o", out.to_str().unwrap()], &release).success();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "id,z,field\n3,0.08,G12\n");
+}
+
+#[test]
+fn keyword_commands_refuse_a_directory() {
+    let (_dir, release) = dataset();
+    dog(&["--list-keywords"], &release)
+        .failure()
+        .stderr(predicate::str::contains("dataset directory"));
+}
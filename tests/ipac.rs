@@ -0,0 +1,86 @@
+use assert_cmd::Command;
+use polars::prelude::{ParquetReader, SerReader};
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+const TABLE: &str = r"\fixlen = T
+\RowsRetrieved = 3
+\CatalogName = 'allwise_p3as_psd'
+\ Search by cone around M31
+|  id  |      ra     |  mag   |   name   |
+|  int |    double   |  real  |   char   |
+|      |     deg     |  mag   |          |
+|  -99 |             |  null  |   null   |
+   1      10.684708    12.5     M31
+  -99     10.5         null     null
+   3                   14.25    NGC 224
+";
+
+fn ipac(name: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    fs::write(&path, TABLE).expect("failed to write ipac table");
+    (dir, path)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn declared_types_and_nulls_are_used() {
+    for name in ["search.tbl", "search_output"] {
+        let (_dir, file) = ipac(name);
+        dog(&["-d"], &file).success().stdout(predicate::eq(
+            "1 10.684708 12.5 M31\n 10.5  \n3  14.25 \"NGC 224\"\n",
+        ));
+        let output = dog(&["--schema"], &file).success().get_output().stdout.clone();
+        let schema = String::from_utf8(output).unwrap();
+        assert!(schema.contains("Int64"), "{schema}");
+        assert!(schema.contains("Float64"), "{schema}");
+    }
+}
+
+#[test]
+fn columns_are_counted_in_characters() {
+    let dir = tempdir().expect("create temp dir");
+    let file = dir.path().join("stars.tbl");
+    fs::write(
+        &file,
+        "|  λ   | flux |  name  |\n| real | real |  char  |\n 5007.0 12.250  α Cen  \n 6563.0 -0.270  Ø Vel  \n",
+    )
+    .unwrap();
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("5007.0 12.25 \"α Cen\"\n6563.0 -0.27 \"Ø Vel\"\n"));
+}
+
+#[test]
+fn keyword_lines_are_read_by_the_keyword_commands() {
+    let (_dir, file) = ipac("search.tbl");
+    dog(&["--list-keywords"], &file)
+        .success()
+        .stdout(predicate::eq("fixlen\nRowsRetrieved\nCatalogName\n"));
+    dog(&["-k", "CatalogName"], &file)
+        .success()
+        .stdout(predicate::eq("allwise_p3as_psd\n"));
+    dog(&["-k", "SKYAREA"], &file)
+        .failure()
+        .stderr(predicate::str::contains("not in the IPAC header"));
+}
+
+#[test]
+fn convert_keeps_units() {
+    let (dir, file) = ipac("search.tbl");
+    dog(&["--convert"], &file).success();
+    let parquet = fs::File::open(dir.path().join("search_converted.parquet")).unwrap();
+    let schema = ParquetReader::new(parquet).schema().unwrap();
+    let ra = schema.get("ra").unwrap().metadata.clone().unwrap();
+    assert_eq!(ra.get("unit").map(|v| v.as_str()), Some("deg"));
+}
@@ -0,0 +1,77 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+const MAML: &str = "dataset: test
+# Fields as of the first release.
+fields:
+- name: id
+  data_type: string
+- name: redshift
+  unit: ''
+  data_type: float64
+version: 1
+";
+
+const NOTES: &str = "Redshifts are spectroscopic.\n";
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+// The test fixture with a maml block and a notes keyword in its footer.
+fn described_parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &parquet).unwrap();
+    for (keyword, text) in [("maml", MAML), ("notes", NOTES)] {
+        let meta = dir.path().join(keyword);
+        fs::write(&meta, text).unwrap();
+        dog(&["--insert-metadata", meta.to_str().unwrap(), keyword], &parquet).success();
+    }
+    (dir, parquet)
+}
+
+#[test]
+fn keywords_are_carried_into_the_subset() {
+    let (dir, parquet) = described_parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-f", "redshift > 0.15", "-o", out.to_str().unwrap()], &parquet).success();
+    dog(&["--list-keywords"], &out)
+        .success()
+        .stdout(predicate::str::contains("maml").and(predicate::str::contains("notes")));
+    dog(&["-k", "notes"], &out)
+        .success()
+        .stdout(predicate::str::contains("spectroscopic"));
+    dog(&["-k", "maml"], &out)
+        .success()
+        .stdout(predicate::str::contains("- name: id").and(predicate::str::contains("- name: redshift")));
+}
+
+#[test]
+fn maml_fields_follow_the_selected_columns() {
+    let (dir, parquet) = described_parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-c", "redshift", "-o", out.to_str().unwrap()], &parquet).success();
+    let maml = dog(&["-k", "maml"], &out).success().get_output().stdout.clone();
+    let maml = String::from_utf8(maml).unwrap();
+    assert!(!maml.contains("- name: id"), "{maml}");
+    assert!(maml.contains("- name: redshift\n  unit: ''\n  data_type: float64\nversion: 1"), "{maml}");
+    assert!(maml.contains("# Fields as of the first release."), "{maml}");
+}
+
+#[test]
+fn drop_keywords_writes_a_bare_subset() {
+    let (dir, parquet) = described_parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-c", "redshift", "--drop-keywords", "-o", out.to_str().unwrap()], &parquet).success();
+    dog(&["--list-keywords"], &out)
+        .success()
+        .stdout(predicate::str::contains("maml").not().and(predicate::str::contains("notes").not()));
+}
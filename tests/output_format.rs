@@ -0,0 +1,96 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+const CSV: &str = "id,redshift\nxyz,0.1\nabc,0.2\n";
+
+fn parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &path).expect("failed to copy parquet to temp dir");
+    (dir, path)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn convert_to_csv() {
+    let (dir, file) = parquet();
+    dog(&["--convert", "--to", "csv"], &file).success();
+    let converted = fs::read_to_string(dir.path().join("test_converted.csv")).unwrap();
+    assert_eq!(converted, CSV);
+}
+
+#[test]
+fn to_overrides_the_outfile_extension() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("table.dat");
+    dog(&["-c", "id,redshift", "--to", "csv", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(fs::read_to_string(&out).unwrap(), CSV);
+
+    let out = dir.path().join("rows.txt");
+    dog(&["-f", "redshift > 0.15", "--to", "ndjson", "-o", out.to_str().unwrap()], &file).success();
+    dog(&["--format", "ndjson", "-d"], &out)
+        .success()
+        .stdout(predicate::eq("abc 0.2\n"));
+}
+
+#[test]
+fn whole_files_are_converted_with_outfile() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("whole.csv");
+    dog(&["-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(fs::read_to_string(&out).unwrap(), CSV);
+
+    // Chains of conversions end where they started.
+    let ecsv = dir.path().join("whole.ecsv");
+    dog(&["-o", ecsv.to_str().unwrap()], &out).success();
+    let back = dir.path().join("back.parquet");
+    dog(&["-o", back.to_str().unwrap()], &ecsv).success();
+    dog(&["-d"], &back)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn copies_still_need_a_selection() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("copy.parquet");
+    dog(&["-o", out.to_str().unwrap()], &file)
+        .failure()
+        .stderr(predicate::str::contains("No columns or rows have been selected"));
+    assert!(!out.exists());
+}
+
+#[test]
+fn converting_to_the_same_format_is_refused() {
+    let (_dir, file) = parquet();
+    dog(&["--convert", "--to", "parquet"], &file)
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("File is already a parquet!"))
+        .stderr(predicate::str::contains("panicked").not());
+}
+
+#[test]
+fn to_needs_somewhere_to_write() {
+    let (_dir, file) = parquet();
+    dog(&["--to", "csv"], &file).failure();
+}
+
+#[test]
+fn ipac_is_not_written() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("table.tbl");
+    dog(&["-c", "id", "-o", out.to_str().unwrap()], &file)
+        .failure()
+        .stderr(predicate::str::contains("can be read but not written"));
+}
@@ -0,0 +1,201 @@
+use assert_cmd::Command;
+use polars::prelude::{col, df, Categories, DataType, IntoLazy, ParquetReader, ParquetWriter, SerReader};
+use polars_parquet::parquet::compression::Compression;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+fn parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &path).expect("failed to copy parquet to temp dir");
+    (dir, path)
+}
+
+// A config file that's never there, so a developer's own can't change the results.
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .env("DOG_CONFIG", "/nonexistent/dog.yaml")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn dog_with_config(config: &PathBuf, args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .env("DOG_CONFIG", config)
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn codec(path: &PathBuf) -> Compression {
+    let mut reader = ParquetReader::new(fs::File::open(path).unwrap());
+    reader.get_metadata().unwrap().row_groups[0].parquet_columns()[0].compression()
+}
+
+// Whether each column of the first row group starts with a dictionary page.
+fn dictionary_pages(path: &PathBuf) -> Vec<bool> {
+    let mut reader = ParquetReader::new(fs::File::open(path).unwrap());
+    reader.get_metadata().unwrap().row_groups[0]
+        .parquet_columns()
+        .iter()
+        .map(|column| column.dictionary_page_offset().is_some())
+        .collect()
+}
+
+/// 200 distinct names, too many for polars to dictionary-encode by itself, a
+/// categorical and an integer column.
+fn names_parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("names.parquet");
+    let names: Vec<String> = (0..200).map(|i| format!("source-{i}")).collect();
+    let bands: Vec<&str> = (0..200).map(|i| ["g", "r", "i"][i % 3]).collect();
+    let mut df = df![
+        "name" => names,
+        "band" => bands,
+        "count" => (0..200i64).map(|i| i % 4).collect::<Vec<_>>(),
+    ]
+    .unwrap()
+    .lazy()
+    .with_column(col("band").cast(DataType::from_categories(Categories::global())))
+    .collect()
+    .unwrap();
+    ParquetWriter::new(fs::File::create(&path).unwrap())
+        .finish(&mut df)
+        .unwrap();
+    (dir, path)
+}
+
+fn row_groups(path: &PathBuf) -> usize {
+    let mut reader = ParquetReader::new(fs::File::open(path).unwrap());
+    reader.get_metadata().unwrap().row_groups.len()
+}
+
+#[test]
+fn row_group_size_splits_the_output() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(
+        &["-c", "id,redshift", "--row-group-size", "1", "--compression", "gzip", "--compression-level", "9", "-o", out.to_str().unwrap()],
+        &file,
+    )
+    .success();
+    assert_eq!(row_groups(&out), 2);
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn statistics_can_be_switched_off() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(
+        &["-c", "id,redshift", "--statistics", "off", "-o", out.to_str().unwrap()],
+        &file,
+    )
+    .success();
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn dictionary_encoding_can_be_switched() {
+    let (dir, file) = names_parquet();
+    let schema = String::from_utf8(dog(&["--schema"], &file).success().get_output().stdout.clone()).unwrap();
+
+    let out = dir.path().join("default.parquet");
+    dog(&["-c", "name,band,count", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(dictionary_pages(&out), [false, true, true]);
+
+    let out = dir.path().join("on.parquet");
+    dog(&["-c", "name,band,count", "--dictionary", "on", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(dictionary_pages(&out), [true, true, true]);
+    dog(&["--schema"], &out).success().stdout(predicate::eq(schema.clone()));
+
+    let out = dir.path().join("off.parquet");
+    dog(&["-c", "name,band,count", "--dictionary", "off", "--row-group-size", "150", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(dictionary_pages(&out), [false, false, false]);
+    assert_eq!(row_groups(&out), 2);
+    dog(&["--schema"], &out)
+        .success()
+        .stdout(predicate::eq(schema.replace("Categorical", "String")));
+    dog(&["-t", "1"], &out)
+        .success()
+        .stdout(predicate::str::contains("source-199"));
+
+    let config = dir.path().join("config.yaml");
+    fs::write(&config, "parquet:\n  dictionary: off\n").unwrap();
+    let out = dir.path().join("configured.parquet");
+    dog_with_config(&config, &["-c", "name,band,count", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(dictionary_pages(&out), [false, false, false]);
+}
+
+#[test]
+fn bad_levels_are_refused() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-c", "id", "--compression-level", "30", "-o", out.to_str().unwrap()], &file)
+        .failure();
+    dog(&["-c", "id", "--compression", "snappy", "--compression-level", "3", "-o", out.to_str().unwrap()], &file)
+        .failure()
+        .stderr(predicate::str::contains("no compression levels"));
+}
+
+#[test]
+fn config_file_sets_defaults_the_command_line_overrides() {
+    let (dir, file) = parquet();
+    let config = dir.path().join("config.yaml");
+    fs::write(&config, "parquet:\n  compression: snappy\n  row-group-size: 1\n  statistics: on\n").unwrap();
+
+    let out = dir.path().join("from_config.parquet");
+    dog_with_config(&config, &["-c", "id,redshift", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(row_groups(&out), 2);
+
+    let out = dir.path().join("overridden.parquet");
+    dog_with_config(&config, &["-c", "id,redshift", "--row-group-size", "10", "-o", out.to_str().unwrap()], &file)
+        .success();
+    assert_eq!(row_groups(&out), 1);
+}
+
+#[test]
+fn a_codec_on_the_command_line_drops_the_configured_level() {
+    let (dir, file) = parquet();
+    let config = dir.path().join("config.yaml");
+    fs::write(&config, "parquet:\n  compression: zstd\n  compression-level: 9\n").unwrap();
+
+    let out = dir.path().join("snappy.parquet");
+    dog_with_config(&config, &["-c", "id", "--compression", "snappy", "-o", out.to_str().unwrap()], &file)
+        .success();
+    assert_eq!(codec(&out), Compression::Snappy);
+
+    let out = dir.path().join("gzip.parquet");
+    dog_with_config(&config, &["-c", "id", "--compression", "gzip", "-o", out.to_str().unwrap()], &file)
+        .success();
+    assert_eq!(codec(&out), Compression::Gzip);
+
+    // Without a codec, a level from the command line goes with the configured one.
+    let out = dir.path().join("zstd.parquet");
+    dog_with_config(&config, &["-c", "id", "--compression-level", "3", "-o", out.to_str().unwrap()], &file)
+        .success();
+    assert_eq!(codec(&out), Compression::Zstd);
+}
+
+#[test]
+fn config_file_typos_are_reported() {
+    let (dir, file) = parquet();
+    let config = dir.path().join("config.yaml");
+    fs::write(&config, "parquet:\n  row-groups: 1\n").unwrap();
+    let out = dir.path().join("sub.parquet");
+    dog_with_config(&config, &["-c", "id", "-o", out.to_str().unwrap()], &file)
+        .failure()
+        .stderr(predicate::str::contains("'row-groups' is not a parquet option"));
+    // Nothing is written, so the config isn't read.
+    dog_with_config(&config, &["-d"], &file).success();
+}
@@ -0,0 +1,97 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+fn parquet() -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &path).expect("failed to copy parquet to temp dir");
+    (dir, path)
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+#[test]
+fn streamed_outputs_hold_the_subset() {
+    let (dir, file) = parquet();
+    for name in ["sub.parquet", "sub.arrow", "sub.csv", "sub.ndjson"] {
+        let out = dir.path().join(name);
+        dog(&["-c", "id,redshift", "-f", "redshift > 0.15", "-o", out.to_str().unwrap()], &file)
+            .success();
+        dog(&["-d"], &out)
+            .success()
+            .stdout(predicate::eq("abc 0.2\n"));
+    }
+}
+
+#[test]
+fn write_errors_are_reported_not_panicked() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-f", "magnitude > 20", "-o", out.to_str().unwrap()], &file)
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("panicked").not());
+}
+
+#[test]
+fn existing_outputs_are_kept_without_force() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.csv");
+    fs::write(&out, "keep me\n").unwrap();
+    dog(&["-c", "id", "-o", out.to_str().unwrap()], &file)
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+    assert_eq!(fs::read_to_string(&out).unwrap(), "keep me\n");
+
+    dog(&["-c", "id", "-F", "-o", out.to_str().unwrap()], &file).success();
+    assert_eq!(fs::read_to_string(&out).unwrap(), "id\nxyz\nabc\n");
+}
+
+#[test]
+fn convert_does_not_clobber_an_earlier_conversion() {
+    let (dir, file) = parquet();
+    dog(&["--convert", "--to", "csv"], &file).success();
+    dog(&["--convert", "--to", "csv"], &file)
+        .failure()
+        .stderr(predicate::str::contains("test_converted.csv' already exists"));
+    dog(&["--convert", "--to", "csv", "--force"], &file).success();
+    assert!(dir.path().join("test_converted.csv").exists());
+}
+
+#[test]
+fn the_input_is_only_replaced_with_force() {
+    let (_dir, file) = parquet();
+    let path = file.to_str().unwrap();
+    dog(&["-f", "redshift > 0.15", "-o", path], &file)
+        .failure()
+        .stderr(predicate::str::contains("is the input file"));
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+
+    dog(&["-f", "redshift > 0.15", "-F", "-o", path], &file).success();
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("abc 0.2\n"));
+}
+
+#[test]
+fn failed_writes_leave_nothing_behind() {
+    let (dir, file) = parquet();
+    let out = dir.path().join("sub.parquet");
+    dog(&["-c", "id", "--compression-level", "99", "-o", out.to_str().unwrap()], &file).failure();
+    let names: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["test.parquet"]);
+}
@@ -0,0 +1,91 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::tempdir;
+
+const CSV: &str = "id,redshift\nxyz,0.1\nabc,0.2\n";
+
+fn dog_stdin(args: &[&str], input: impl Into<Vec<u8>>) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg("-")
+        .write_stdin(input)
+        .assert()
+}
+
+#[test]
+fn csv_is_read_from_stdin() {
+    dog_stdin(&["-d"], CSV)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+    dog_stdin(&["-f", "redshift > 0.15", "-d"], CSV)
+        .success()
+        .stdout(predicate::eq("abc 0.2\n"));
+}
+
+#[test]
+fn json_output_chains_into_another_dog() {
+    let first = Command::cargo_bin("dog")
+        .unwrap()
+        .args(["--json", "tests/fixtures/test.parquet"])
+        .output()
+        .unwrap();
+    assert!(first.status.success());
+    dog_stdin(&["-c", "id,redshift", "-d"], first.stdout)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn parquet_is_spooled_for_its_footer() {
+    let parquet = fs::read("tests/fixtures/test.parquet").unwrap();
+    dog_stdin(&["-c", "id,redshift", "-d"], parquet)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn text_cut_mid_character_is_still_sniffed() {
+    // The é straddles the 512 bytes that are looked at.
+    let rows = format!("{{\"name\":\"a\"}}\n{{\"name\":\"{}é\"}}\n", "a".repeat(489));
+    assert_eq!(rows.find('é'), Some(511));
+    dog_stdin(&["-n"], rows)
+        .success()
+        .stdout(predicate::eq("name\n"));
+}
+
+#[test]
+fn format_overrides_sniffing() {
+    dog_stdin(&["--format", "csv", "--separator", "|", "-d"], "id|n\n[a]|1\n")
+        .success()
+        .stdout(predicate::eq("[a] 1\n"));
+}
+
+#[test]
+fn convert_names_the_output_after_stdin() {
+    let dir = tempdir().expect("create temp dir");
+    Command::cargo_bin("dog")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["--convert", "-"])
+        .write_stdin(CSV)
+        .assert()
+        .success();
+    let converted = dir.path().join("stdin_converted.parquet");
+    Command::cargo_bin("dog")
+        .unwrap()
+        .args(["-d"])
+        .arg(&converted)
+        .assert()
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}
+
+#[test]
+fn stdin_can_only_be_read_once() {
+    dog_stdin(&["-d", "-"], CSV)
+        .failure()
+        .stderr(predicate::str::contains("only be read once"));
+}
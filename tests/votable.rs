@@ -0,0 +1,233 @@
+use assert_cmd::Command;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use polars::prelude::{df, NamedFrom, ParquetReader, ParquetWriter, SerReader, Series};
+use predicates::prelude::*;
+use std::{fs, path::PathBuf};
+use tempfile::{tempdir, TempDir};
+
+const FIELDS: &str = r#"
+<FIELD name="id" datatype="int">
+  <VALUES null="-1"/>
+</FIELD>
+<FIELD name="flux" datatype="double" unit="mJy" ucd="phot.flux.density">
+  <DESCRIPTION>Peak flux &amp; error</DESCRIPTION>
+</FIELD>
+<FIELD name="name" datatype="char" arraysize="*"/>
+"#;
+
+fn votable(name: &str, data: &str) -> (TempDir, PathBuf) {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join(name);
+    let xml = format!(
+        r#"<?xml version="1.0"?>
+<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">
+<RESOURCE>
+<TABLE name="sources">
+{FIELDS}
+<DATA>
+{data}
+</DATA>
+</TABLE>
+</RESOURCE>
+</VOTABLE>
+"#
+    );
+    fs::write(&path, xml).expect("failed to write votable");
+    (dir, path)
+}
+
+fn tabledata_votable() -> (TempDir, PathBuf) {
+    votable(
+        "sources.vot",
+        "<TABLEDATA>
+<TR><TD>1</TD><TD>1.5</TD><TD>M&amp;31</TD></TR>
+<TR><TD>-1</TD><TD></TD><TD>NGC 224</TD></TR>
+<TR><TD>3</TD><TD>NaN</TD><TD/></TR>
+</TABLEDATA>",
+    )
+}
+
+fn binary2_votable() -> (TempDir, PathBuf) {
+    let mut rows = Vec::new();
+    // Null flags, then id, flux and a counted name, all big-endian.
+    rows.push(0x00);
+    rows.extend(1i32.to_be_bytes());
+    rows.extend(1.5f64.to_be_bytes());
+    rows.extend(3u32.to_be_bytes());
+    rows.extend(b"M31");
+    rows.push(0x40);
+    rows.extend(2i32.to_be_bytes());
+    rows.extend(0f64.to_be_bytes());
+    rows.extend(0u32.to_be_bytes());
+    let stream = BASE64_STANDARD.encode(rows);
+    votable(
+        "binary_output",
+        &format!(r#"<BINARY2><STREAM encoding="base64">{stream}</STREAM></BINARY2>"#),
+    )
+}
+
+fn dog(args: &[&str], file: &PathBuf) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("dog")
+        .unwrap()
+        .env("NO_COLOR", "1")
+        .args(args)
+        .arg(file)
+        .assert()
+}
+
+fn stdout_of(args: &[&str], file: &PathBuf) -> String {
+    let output = dog(args, file).success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn tabledata_is_read_with_nulls() {
+    let (_dir, file) = tabledata_votable();
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("1 1.5 M&31\n  \"NGC 224\"\n3  \n"));
+    let schema = stdout_of(&["--schema"], &file);
+    assert!(schema.contains("Int32"), "{schema}");
+    assert!(schema.contains("Float64"), "{schema}");
+}
+
+#[test]
+fn binary2_null_flags_are_read() {
+    let (_dir, file) = binary2_votable();
+    dog(&["-d"], &file)
+        .success()
+        .stdout(predicate::eq("1 1.5 M31\n2  \"\"\n"));
+}
+
+#[test]
+fn array_fields_become_array_and_list_columns() {
+    let dir = tempdir().expect("create temp dir");
+    let path = dir.path().join("arrays.xml");
+    fs::write(
+        &path,
+        r#"<VOTABLE><RESOURCE><TABLE>
+<FIELD name="pos" datatype="float" arraysize="2"/>
+<FIELD name="samples" datatype="short" arraysize="*"/>
+<FIELD name="z" datatype="doubleComplex"/>
+<DATA><TABLEDATA>
+<TR><TD>10.5 -3</TD><TD>1 2 3</TD><TD>1 -1</TD></TR>
+<TR><TD>11 4.5</TD><TD></TD><TD>0 2</TD></TR>
+</TABLEDATA></DATA></TABLE></RESOURCE></VOTABLE>
+"#,
+    )
+    .unwrap();
+    let schema = stdout_of(&["--schema"], &path);
+    assert!(schema.contains("Array"), "{schema}");
+    assert!(schema.contains("List"), "{schema}");
+    assert!(schema.contains("Struct"), "{schema}");
+    dog(&["-d"], &path).success().stdout(predicate::eq(
+        "[10.5,-3.0] [1,2,3] {1.0,-1.0}\n[11.0,4.5] [] {0.0,2.0}\n",
+    ));
+
+    let out = dir.path().join("arrays_copy.vot");
+    dog(&["-c", "pos,samples,z", "-o", out.to_str().unwrap()], &path).success();
+    let xml = fs::read_to_string(&out).unwrap();
+    assert!(
+        xml.contains(r#"<FIELD name="pos" datatype="float" arraysize="2"/>"#),
+        "{xml}"
+    );
+    assert!(
+        xml.contains("<TR><TD>10.5 -3.0</TD><TD>1 2 3</TD><TD>1.0 -1.0</TD></TR>"),
+        "{xml}"
+    );
+}
+
+#[test]
+fn null_array_elements_round_trip() {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = dir.path().join("samples.parquet");
+    let hits = Series::new("hits".into(), [Some(1i32), None, Some(3)]);
+    let flags = Series::new("flags".into(), [Some(true), None]);
+    let mut df = df![
+        "id" => [Some(1i64), None],
+        "hits" => [hits.clone(), hits.slice(0, 1)],
+        "flags" => [flags.clone(), flags.slice(1, 1)],
+    ]
+    .unwrap();
+    ParquetWriter::new(fs::File::create(&parquet).unwrap()).finish(&mut df).unwrap();
+
+    dog(&["--convert", "--to", "votable"], &parquet).success();
+    let converted = dir.path().join("samples_converted.vot");
+    let xml = fs::read_to_string(&converted).unwrap();
+    assert!(
+        xml.contains(&format!("<VALUES null=\"{}\"/>", i32::MIN)),
+        "{xml}"
+    );
+    assert!(xml.contains(r#"<FIELD name="id" datatype="long"/>"#), "{xml}");
+    assert!(xml.contains("<TR><TD></TD><TD>1</TD><TD>?</TD></TR>"), "{xml}");
+    assert!(!xml.contains("NaN"), "{xml}");
+    assert_eq!(stdout_of(&["-d"], &converted), stdout_of(&["-d"], &parquet));
+}
+
+#[test]
+fn uint64_beyond_long_is_refused() {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = dir.path().join("ids.parquet");
+    let mut df = df!["id" => [0u64, i64::MAX as u64]].unwrap();
+    ParquetWriter::new(fs::File::create(&parquet).unwrap()).finish(&mut df).unwrap();
+    dog(&["--convert", "--to", "votable"], &parquet).success();
+    let converted = dir.path().join("ids_converted.vot");
+    assert_eq!(stdout_of(&["-d"], &converted), stdout_of(&["-d"], &parquet));
+
+    let mut df = df!["id" => [0u64, u64::MAX]].unwrap();
+    ParquetWriter::new(fs::File::create(&parquet).unwrap()).finish(&mut df).unwrap();
+    dog(&["--convert", "--to", "votable", "--force"], &parquet)
+        .failure()
+        .stderr(predicate::str::contains("column 'id'"))
+        .stderr(predicate::str::contains("too large for a VOTable long"));
+}
+
+#[test]
+fn outfile_writes_a_votable() {
+    let (dir, file) = tabledata_votable();
+    let out = dir.path().join("bright.vot");
+    dog(&["-f", "id > 0", "-o", out.to_str().unwrap()], &file).success();
+    let xml = fs::read_to_string(&out).unwrap();
+    assert!(xml.contains(r#"<TABLE name="sources">"#), "{xml}");
+    assert!(
+        xml.contains(r#"<FIELD name="flux" datatype="double" unit="mJy" ucd="phot.flux.density">"#),
+        "{xml}"
+    );
+    assert!(
+        xml.contains("<DESCRIPTION>Peak flux &amp; error</DESCRIPTION>"),
+        "{xml}"
+    );
+    dog(&["-d"], &out)
+        .success()
+        .stdout(predicate::eq("1 1.5 M&31\n3  \n"));
+}
+
+#[test]
+fn convert_keeps_field_descriptions() {
+    let (dir, file) = tabledata_votable();
+    dog(&["--convert"], &file).success();
+    let parquet = fs::File::open(dir.path().join("sources_converted.parquet")).unwrap();
+    let schema = ParquetReader::new(parquet).schema().unwrap();
+    let flux = schema.get("flux").unwrap().metadata.clone().unwrap();
+    assert_eq!(flux.get("unit").map(|v| v.as_str()), Some("mJy"));
+    assert_eq!(
+        flux.get("description").map(|v| v.as_str()),
+        Some("Peak flux & error")
+    );
+
+    dog(&["--convert", "--to", "votable"], &file)
+        .failure()
+        .stderr(predicate::str::contains("already a VOTable"));
+}
+
+#[test]
+fn parquet_converts_to_votable() {
+    let dir = tempdir().expect("create temp dir");
+    let parquet = dir.path().join("test.parquet");
+    fs::copy("tests/fixtures/test.parquet", &parquet).unwrap();
+    dog(&["--convert", "--to", "votable"], &parquet).success();
+    let converted = dir.path().join("test_converted.vot");
+    dog(&["-c", "id,redshift", "-d"], &converted)
+        .success()
+        .stdout(predicate::eq("xyz 0.1\nabc 0.2\n"));
+}